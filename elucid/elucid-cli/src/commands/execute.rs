@@ -44,8 +44,9 @@ impl ExecuteCommand {
         data_dir_path: P,
     ) -> anyhow::Result<()> {
         let context = Context::new(data_dir_path);
-        let data = context.execute(&source).await?;
-        data.show().await?;
+        for data in context.execute_script(source).await? {
+            data.show().await?;
+        }
 
         Ok(())
     }
@@ -55,7 +55,7 @@ fn validate_query_input<R: Read>(mut input: R) -> anyhow::Result<()> {
     let _ = input.read_to_end(&mut buffer)?;
     let source = String::from_utf8(buffer)?;
 
-    match parser::check(&source) {
+    match parser::check_script(&source) {
         Ok(_) => Ok(()),
         Err(error) => Ok(error.eprint(&source)?),
     }
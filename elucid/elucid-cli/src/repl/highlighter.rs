@@ -1,4 +1,4 @@
-use elucid_language::lexer::{tokenizer, Token};
+use elucid_language::lexer::{tokenizer, Token, CONTEXTUAL_COMMANDS};
 use nu_ansi_term::{Color, Style};
 use reedline::{Highlighter, StyledText};
 
@@ -8,6 +8,7 @@ impl Highlighter for QueryHighlighter {
     fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
         let mut styled_text = StyledText::new();
         let mut last_end = 0;
+        let mut after_pipe = false;
 
         for (token, span) in tokenizer(line) {
             if span.start() > last_end {
@@ -15,11 +16,31 @@ impl Highlighter for QueryHighlighter {
             }
 
             let style = match token {
-                Token::KeywordSource
+                Token::Identifier(name) if after_pipe && CONTEXTUAL_COMMANDS.contains(&name) => {
+                    Style::new().fg(Color::LightBlue).bold()
+                }
+
+                Token::KeywordLet
+                | Token::KeywordSource
                 | Token::KeywordWhere
                 | Token::KeywordSort
                 | Token::KeywordLimit
-                | Token::KeywordAggregate => Style::new().fg(Color::LightBlue).bold(),
+                | Token::KeywordAggregate
+                | Token::KeywordFields
+                | Token::KeywordEval
+                | Token::KeywordRename
+                | Token::KeywordTimechart
+                | Token::KeywordEventStats
+                | Token::KeywordStreamStats
+                | Token::KeywordDedup
+                | Token::KeywordParse
+                | Token::KeywordGrok
+                | Token::KeywordFillNull
+                | Token::KeywordUnnest
+                | Token::KeywordCompare
+                | Token::KeywordLookup
+                | Token::KeywordJoin
+                | Token::KeywordOn => Style::new().fg(Color::LightBlue).bold(),
 
                 Token::OperatorAdd
                 | Token::OperatorSubtract
@@ -33,17 +54,34 @@ impl Highlighter for QueryHighlighter {
                 | Token::OperatorLessThanOrEqual
                 | Token::OperatorAnd
                 | Token::OperatorOr
+                | Token::OperatorNot
+                | Token::OperatorRegexMatch
+                | Token::OperatorRegexNotMatch
+                | Token::KeywordIn
+                | Token::KeywordBetween
+                | Token::KeywordLike
+                | Token::KeywordIlike
+                | Token::KeywordIs
                 | Token::OperatorAssign
+                | Token::Semicolon
+                | Token::DoubleColon
                 | Token::Pipe => Style::new().fg(Color::Yellow),
 
                 Token::StringLiteral(_) => Style::new().fg(Color::Green),
-                Token::Integer(_) => Style::new().fg(Color::LightCyan),
+                Token::Integer(_)
+                | Token::Float(_)
+                | Token::Duration(_)
+                | Token::Timestamp(_)
+                | Token::KeywordTrue
+                | Token::KeywordFalse
+                | Token::KeywordNull => Style::new().fg(Color::LightCyan),
 
                 _ => Style::new().fg(Color::White),
             };
 
             styled_text.push((style, line[span.start()..span.end()].to_owned()));
             last_end = span.end();
+            after_pipe = token == Token::Pipe;
         }
 
         if last_end < line.len() {
@@ -1,4 +1,5 @@
 mod highlighter;
+#[allow(clippy::module_inception)]
 mod repl;
 mod validator;
 
@@ -34,9 +34,11 @@ pub async fn start(context: &Context) -> anyhow::Result<()> {
                     continue;
                 }
 
-                match context.execute(input).await {
-                    Ok(data) => {
-                        data.show().await?;
+                match context.execute_script(input).await {
+                    Ok(results) => {
+                        for data in results {
+                            data.show().await?;
+                        }
                     }
                     Err(_) => {
                         // Engine prints error itself.
@@ -1,65 +1,1417 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::iter;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
+use datafusion::arrow::array::AsArray;
+use datafusion::arrow::compute::{self, cast};
+use datafusion::arrow::datatypes::{DataType, SchemaRef, TimestampNanosecondType};
+use datafusion::catalog::TableProvider;
+use datafusion::common::runtime::SpawnedTask;
+use datafusion::datasource::empty::EmptyTable;
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
 use datafusion::error::{DataFusionError, Result};
-use datafusion::prelude::{DataFrame, SessionConfig, SessionContext, *};
-use elucid_language::parser;
+use datafusion::logical_expr::AggregateUDF;
+use datafusion::parquet::arrow::arrow_reader::statistics::StatisticsConverter;
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use datafusion::prelude::{CsvReadOptions, DataFrame, SessionConfig, SessionContext};
+use elucid_language::{parser, Command, ParserError, Query, Script, Statement};
 
+use crate::functions::Drain;
+use crate::grok::GrokPatterns;
 use crate::planner::QueryPlanner;
+use crate::time::{self, TimeWindow, TIMESTAMP_DATA_TYPE};
+
+/// Directory within the data directory holding user-defined grok pattern files.
+const GROK_PATTERNS_DIRECTORY: &str = "patterns";
+
+/// Parquet files of a table directory with their modification times, sorted by path.
+type Listing = Vec<(PathBuf, SystemTime)>;
+
+/// Bounds of the timestamp column of a file, in nanoseconds since the Unix epoch.
+type TimeBounds = Option<(i64, i64)>;
 
 pub struct Context {
     context: SessionContext,
     data_dir_path: PathBuf,
+    timestamp_column: String,
+    /// Schemas inferred for tables, kept along with the listing they were inferred from.
+    schemas: Mutex<HashMap<String, (Listing, SchemaRef)>>,
+    /// Time bounds read from Parquet footers, kept along with the modification time of the file.
+    file_bounds: Mutex<HashMap<PathBuf, (SystemTime, TimeBounds)>>,
 }
 
 impl Context {
     pub fn new<P: AsRef<Path>>(data_dir_path: P) -> Self {
         let config = SessionConfig::new().with_information_schema(true);
         let context = SessionContext::new_with_config(config);
+        context.register_udaf(AggregateUDF::from(Drain::new()));
         Self {
             context,
             data_dir_path: data_dir_path.as_ref().to_owned(),
+            timestamp_column: "_time".to_owned(),
+            schemas: Mutex::new(HashMap::new()),
+            file_bounds: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Sets the column time ranges on `source` are applied to. Defaults to `_time`.
+    pub fn with_timestamp_column(mut self, timestamp_column: &str) -> Self {
+        self.timestamp_column = timestamp_column.to_owned();
+        self.file_bounds.get_mut().unwrap().clear();
+        self
+    }
+
     pub async fn execute(&self, source: &str) -> Result<DataFrame> {
-        let query = parser::parse(source).map_err(|error| {
-            match error.eprint(source) {
-                Ok(()) => {}
-                Err(error) => return DataFusionError::IoError(error),
-            };
-            DataFusionError::Plan(format!("Parse error: {:?}", error))
-        })?;
+        let query = parser::parse(source).map_err(|error| parse_error(source, error))?;
+        self.execute_query(query, &HashSet::new()).await
+    }
+
+    /// Executes the statements of a script in order and returns the result of every query that
+    /// isn't bound by `let`. Bindings are registered as views for the rest of the script.
+    pub async fn execute_script(&self, source: &str) -> Result<Vec<DataFrame>> {
+        let script = parser::parse_script(source).map_err(|error| parse_error(source, error))?;
+
+        let mut bindings = HashSet::new();
+        let results = self.execute_statements(script, &mut bindings).await;
+        // The plans of the results hold the views themselves, so they outlive the names.
+        for name in &bindings {
+            self.context.deregister_table(name)?;
+        }
+        results
+    }
 
-        if !self.context.table_exist(&query.source)? {
-            self.register_table(&query.source).await?;
+    async fn execute_statements(
+        &self,
+        script: Script,
+        bindings: &mut HashSet<String>,
+    ) -> Result<Vec<DataFrame>> {
+        let mut results = Vec::new();
+        for statement in script.statements {
+            match statement {
+                Statement::Let { name, query } => {
+                    let data = self.execute_query(query, bindings).await?;
+                    self.context.deregister_table(&name)?;
+                    self.context.register_table(&name, data.into_view())?;
+                    bindings.insert(name);
+                }
+                Statement::Query(query) => results.push(self.execute_query(query, bindings).await?),
+            }
+        }
+        Ok(results)
+    }
+
+    /// Plans a query, registering the tables it reads except those bound earlier in a script.
+    async fn execute_query(&self, query: Query, bindings: &HashSet<String>) -> Result<DataFrame> {
+        let mut table_names = self.event_table_names()?;
+        table_names.extend(bindings.iter().cloned());
+        let planner = QueryPlanner::new(&self.context, &self.timestamp_column)
+            .with_grok_patterns(self.grok_patterns()?)
+            .with_tables(table_names);
+        let mut tables = HashMap::new();
+        collect_tables(&planner, &query, &mut tables)?;
+        for (table_name, window) in tables {
+            if !bindings.contains(&table_name) {
+                self.register_table(&table_name, window).await?;
+            }
         }
 
-        let planner = QueryPlanner::new(&self.context);
         let plan = planner.create_logical_plan(query).await?;
 
-        self.context
-            .execute_logical_plan(plan)
-            .await
-            .map_err(|error| error.into())
+        self.context.execute_logical_plan(plan).await
     }
 
-    async fn register_table(&self, table_name: &str) -> Result<()> {
+    /// Registers the table, replacing any previous registration. A table is either a directory
+    /// of Parquet files or a CSV file named after the table. If a time window is given, Parquet
+    /// files whose timestamp statistics lie entirely outside of it are left out of the table.
+    /// Schemas and statistics are cached until the files of the table change.
+    async fn register_table(&self, table_name: &str, window: Option<TimeWindow>) -> Result<()> {
         let table_path = self.data_dir_path.join(table_name);
-        if !table_path.exists() {
-            return Err(DataFusionError::Execution(format!(
-                "Table '{}' does not exist (directory not found: {:?})",
-                table_name, table_path,
-            )));
+        if !table_path.is_dir() {
+            let csv_path = self.data_dir_path.join(format!("{}.csv", table_name));
+            if !csv_path.is_file() {
+                return Err(DataFusionError::Execution(format!(
+                    "Table '{}' does not exist (neither directory {:?} nor file {:?} found)",
+                    table_name, table_path, csv_path,
+                )));
+            }
+            self.context.deregister_table(table_name)?;
+            return self
+                .context
+                .register_csv(table_name, path_to_str(&csv_path)?, CsvReadOptions::new())
+                .await;
         }
-        let table_path_str = table_path
-            .to_str()
-            .ok_or(DataFusionError::Execution("Invalid table path".to_owned()))?;
+        let listing = list_parquet_files(&table_path)?;
+
+        let format = ParquetFormat::default().with_enable_pruning(true);
+        let options = ListingOptions::new(Arc::new(format)).with_file_extension(".parquet");
+        // The schema is inferred from every file, so it doesn't change with the time window.
+        let cached_schema = self
+            .schemas
+            .lock()
+            .unwrap()
+            .get(table_name)
+            .filter(|(cached_listing, _)| *cached_listing == listing)
+            .map(|(_, schema)| schema.clone());
+        let schema = match cached_schema {
+            Some(schema) => schema,
+            None => {
+                let table_url = ListingTableUrl::parse(path_to_str(&table_path)?)?;
+                let schema = options
+                    .infer_schema(&self.context.state(), &table_url)
+                    .await?;
+                self.schemas
+                    .lock()
+                    .unwrap()
+                    .insert(table_name.to_owned(), (listing.clone(), schema.clone()));
+                schema
+            }
+        };
 
-        let options = ParquetReadOptions::new().parquet_pruning(true);
-        self.context
-            .register_parquet(table_name, table_path_str, options)
-            .await?;
+        let file_paths: Vec<&PathBuf> = match window {
+            Some(window) => {
+                let bounds = self.time_bounds(&listing).await?;
+                listing
+                    .iter()
+                    .zip(bounds)
+                    .filter(|(_, bounds)| bounds.is_none_or(|(min, max)| window.overlaps(min, max)))
+                    .map(|((file_path, _), _)| file_path)
+                    .collect()
+            }
+            None => listing.iter().map(|(file_path, _)| file_path).collect(),
+        };
+        let file_urls = file_paths
+            .into_iter()
+            .map(|file_path| ListingTableUrl::parse(path_to_str(file_path)?))
+            .collect::<Result<Vec<_>>>()?;
+
+        // A listing table needs at least one path, so a window no file overlaps reads nothing.
+        let table: Arc<dyn TableProvider> = if file_urls.is_empty() {
+            Arc::new(EmptyTable::new(schema))
+        } else {
+            let config = ListingTableConfig::new_with_multi_paths(file_urls)
+                .with_listing_options(options)
+                .with_schema(schema);
+            Arc::new(ListingTable::try_new(config)?)
+        };
+
+        self.context.deregister_table(table_name)?;
+        self.context.register_table(table_name, table)?;
 
         Ok(())
     }
+
+    /// Lists the tables of events in the data directory: every directory except the one of the
+    /// grok patterns. CSV lookup tables are left out, so that wildcards don't match them.
+    fn event_table_names(&self) -> Result<Vec<String>> {
+        if !self.data_dir_path.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut table_names = Vec::new();
+        for entry in fs::read_dir(&self.data_dir_path)? {
+            let path = entry?.path();
+            let table_name = path
+                .file_name()
+                .filter(|file_name| path.is_dir() && *file_name != GROK_PATTERNS_DIRECTORY);
+            table_names.extend(
+                table_name
+                    .and_then(|table_name| table_name.to_str())
+                    .map(str::to_owned),
+            );
+        }
+        table_names.sort();
+        Ok(table_names)
+    }
+
+    /// Loads the built-in grok patterns, extended by the `*.grok` files in the `patterns`
+    /// directory of the data directory. Files are read on every query, so edits apply at once.
+    fn grok_patterns(&self) -> Result<GrokPatterns> {
+        let mut patterns = GrokPatterns::default();
+        let patterns_path = self.data_dir_path.join(GROK_PATTERNS_DIRECTORY);
+        if !patterns_path.is_dir() {
+            return Ok(patterns);
+        }
+
+        let mut file_paths = Vec::new();
+        for entry in fs::read_dir(&patterns_path)? {
+            let file_path = entry?.path();
+            if file_path
+                .extension()
+                .is_some_and(|extension| extension == "grok")
+            {
+                file_paths.push(file_path);
+            }
+        }
+        file_paths.sort();
+
+        for file_path in file_paths {
+            patterns
+                .add_definitions(&fs::read_to_string(&file_path)?)
+                .map_err(|error| error.context(format!("In {:?}", file_path)))?;
+        }
+        Ok(patterns)
+    }
+
+    /// Returns the time bounds of every file of the listing, or `None` where they are unknown.
+    /// Footers are only read for files that changed since their bounds were cached, off the
+    /// async threads as the reads block.
+    async fn time_bounds(&self, listing: &Listing) -> Result<Vec<TimeBounds>> {
+        let stale: Listing = {
+            let file_bounds = self.file_bounds.lock().unwrap();
+            listing
+                .iter()
+                .filter(|(file_path, modified)| {
+                    file_bounds
+                        .get(file_path)
+                        .is_none_or(|(cached, _)| cached != modified)
+                })
+                .cloned()
+                .collect()
+        };
+        if !stale.is_empty() {
+            let timestamp_column = self.timestamp_column.clone();
+            let read = SpawnedTask::spawn_blocking(move || {
+                stale
+                    .into_iter()
+                    .map(|(file_path, modified)| {
+                        // A file whose footer can't be read is never pruned.
+                        let bounds = read_time_bounds(&file_path, &timestamp_column)
+                            .ok()
+                            .flatten();
+                        (file_path, (modified, bounds))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .join_unwind()
+            .await
+            .map_err(|error| DataFusionError::ExecutionJoin(Box::new(error)))?;
+            self.file_bounds.lock().unwrap().extend(read);
+        }
+
+        let file_bounds = self.file_bounds.lock().unwrap();
+        Ok(listing
+            .iter()
+            .map(|(file_path, _)| file_bounds.get(file_path).and_then(|(_, bounds)| *bounds))
+            .collect())
+    }
+}
+
+/// Lists the Parquet files of a table directory, sorted by path.
+fn list_parquet_files(table_path: &Path) -> Result<Listing> {
+    let mut listing = Vec::new();
+    for entry in fs::read_dir(table_path)? {
+        let entry = entry?;
+        let file_path = entry.path();
+        if file_path
+            .extension()
+            .is_some_and(|extension| extension == "parquet")
+        {
+            listing.push((file_path, entry.metadata()?.modified()?));
+        }
+    }
+    listing.sort();
+    Ok(listing)
+}
+
+/// Reads the bounds of the timestamp column from the statistics in the Parquet footer,
+/// without reading any data pages. Only timestamp columns have bounds: the statistics of
+/// string columns compare the text, which doesn't order timestamps with different offsets.
+fn read_time_bounds(file_path: &Path, timestamp_column: &str) -> Result<TimeBounds> {
+    let file = File::open(file_path)?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let is_timestamp = builder
+        .schema()
+        .field_with_name(timestamp_column)
+        .is_ok_and(|field| matches!(field.data_type(), DataType::Timestamp(_, _)));
+    if !is_timestamp {
+        return Ok(None);
+    }
+    let converter =
+        StatisticsConverter::try_new(timestamp_column, builder.schema(), builder.parquet_schema())?;
+    let row_groups = builder.metadata().row_groups();
+
+    let mins = cast(&converter.row_group_mins(row_groups)?, &TIMESTAMP_DATA_TYPE)?;
+    let maxes = cast(
+        &converter.row_group_maxes(row_groups)?,
+        &TIMESTAMP_DATA_TYPE,
+    )?;
+    if mins.null_count() > 0 || maxes.null_count() > 0 {
+        return Ok(None);
+    }
+
+    let min = compute::min(mins.as_primitive::<TimestampNanosecondType>());
+    let max = compute::max(maxes.as_primitive::<TimestampNanosecondType>());
+    Ok(min.zip(max))
+}
+
+/// Collects every table the query reads, including those of lookups and sub-queries, with a
+/// time window covering all reads of the table. `None` stands for the whole table.
+fn collect_tables(
+    planner: &QueryPlanner,
+    query: &Query,
+    tables: &mut HashMap<String, Option<TimeWindow>>,
+) -> Result<()> {
+    // `compare` reads the tables again over windows shifted back in time.
+    let mut shifts = vec![0];
+    for command in &query.commands {
+        if let Command::Compare(offsets) = command {
+            shifts = shifts
+                .iter()
+                .flat_map(|shift| {
+                    iter::once(Ok(*shift)).chain(
+                        offsets
+                            .iter()
+                            .map(move |offset| time::add_duration(*shift, *offset)),
+                    )
+                })
+                .collect::<Result<_>>()?;
+        }
+    }
+    for table_name in planner.resolve_sources(&query.sources)? {
+        for shift in &shifts {
+            let window = query
+                .time_range
+                .as_ref()
+                .map(|time_range| planner.time_window(time_range, *shift))
+                .transpose()?;
+            add_table(tables, &table_name, window);
+        }
+    }
+    for command in &query.commands {
+        match command {
+            Command::Lookup { table, .. } => add_table(tables, table, None),
+            Command::Join { query, .. } => collect_tables(planner, query, tables)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn add_table(
+    tables: &mut HashMap<String, Option<TimeWindow>>,
+    table_name: &str,
+    window: Option<TimeWindow>,
+) {
+    tables
+        .entry(table_name.to_owned())
+        .and_modify(|existing| *existing = existing.zip(window).map(|(a, b)| a.hull(&b)))
+        .or_insert(window);
+}
+
+fn parse_error(source: &str, error: ParserError) -> DataFusionError {
+    match error.eprint(source) {
+        Ok(()) => {}
+        Err(error) => return DataFusionError::IoError(error),
+    };
+    DataFusionError::Plan(format!("Parse error: {:?}", error))
+}
+
+fn path_to_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or(DataFusionError::Execution("Invalid table path".to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use datafusion::arrow::array::{
+        ArrayRef, Int64Array, ListArray, RecordBatch, StringArray, TimestampNanosecondArray,
+    };
+    use datafusion::arrow::datatypes::Int64Type;
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use datafusion::parquet::arrow::ArrowWriter;
+
+    use super::*;
+    use crate::time::{now_nanos, parse_timestamp};
+
+    /// Data directory in the temporary directory, removed again at the end of the test.
+    struct TestData {
+        path: PathBuf,
+    }
+
+    impl TestData {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("elucid-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self { path }
+        }
+
+        /// Writes the columns as a Parquet file of the table.
+        fn write(&self, table_name: &str, file_name: &str, columns: Vec<(&str, ArrayRef)>) {
+            let table_path = self.path.join(table_name);
+            fs::create_dir_all(&table_path).unwrap();
+            let batch = RecordBatch::try_from_iter(columns).unwrap();
+            let file = File::create(table_path.join(format!("{}.parquet", file_name))).unwrap();
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+        }
+
+        /// Writes the text as the CSV file of a lookup table.
+        fn write_csv(&self, table_name: &str, contents: &str) {
+            fs::write(self.path.join(format!("{}.csv", table_name)), contents).unwrap();
+        }
+
+        fn context(&self) -> Context {
+            Context::new(&self.path)
+        }
+    }
+
+    impl Drop for TestData {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    fn timestamps(timestamps: &[&str]) -> ArrayRef {
+        Arc::new(TimestampNanosecondArray::from_iter_values(
+            timestamps
+                .iter()
+                .map(|timestamp| parse_timestamp(timestamp).unwrap()),
+        ))
+    }
+
+    async fn execute(context: &Context, source: &str) -> String {
+        let batches = context
+            .execute(source)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        pretty_format_batches(&batches).unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn compare_lines_up_time_buckets() {
+        let data = TestData::new("compare_lines_up_time_buckets");
+        let time = timestamps(&[
+            "2026-10-16T12:05Z",
+            "2026-10-16T12:20Z",
+            "2026-10-16T12:45Z",
+            "2026-10-17T12:10Z",
+            "2026-10-17T12:40Z",
+            "2026-10-17T12:50Z",
+        ]);
+        data.write("api", "a", vec![("_time", time)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api from 2026-10-17T12:00Z to 2026-10-17T13:00Z \
+                 | aggr n = count() by bin(_time, 30m) | compare 1d",
+            )
+            .await,
+            "+---------------------+---+------+------------+\n\
+             | _time               | n | n_1d | n_delta_1d |\n\
+             +---------------------+---+------+------------+\n\
+             | 2026-10-17T12:00:00 | 1 | 2    | -1         |\n\
+             | 2026-10-17T12:30:00 | 2 | 1    | 1          |\n\
+             +---------------------+---+------+------------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn compare_since_excludes_the_present() {
+        const MINUTE: i64 = 60_000_000_000;
+        const DAY: i64 = 24 * 60 * MINUTE;
+        let data = TestData::new("compare_since_excludes_the_present");
+        let now = now_nanos();
+        let time: ArrayRef = Arc::new(TimestampNanosecondArray::from(vec![
+            now - 10 * MINUTE,
+            now - 20 * MINUTE,
+            now - DAY - 10 * MINUTE,
+        ]));
+        let status: ArrayRef = Arc::new(StringArray::from(vec!["ok", "error", "ok"]));
+        data.write("worker", "a", vec![("_time", time), ("status", status)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source worker since 1h | aggr c = count() by status | compare 1d, 2d",
+            )
+            .await,
+            "+--------+---+------+------------+------+------------+\n\
+             | status | c | c_1d | c_delta_1d | c_2d | c_delta_2d |\n\
+             +--------+---+------+------------+------+------------+\n\
+             | error  | 1 |      |            |      |            |\n\
+             | ok     | 1 | 1    | 0          |      |            |\n\
+             +--------+---+------+------------+------+------------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn bin_groups_are_named_after_their_field() {
+        let data = TestData::new("bin_groups_are_named_after_their_field");
+        let time = timestamps(&[
+            "2026-10-17T12:05Z",
+            "2026-10-17T12:08Z",
+            "2026-10-17T12:15Z",
+        ]);
+        let bytes: ArrayRef = Arc::new(Int64Array::from(vec![120, 180, 250]));
+        data.write("api", "a", vec![("_time", time), ("bytes", bytes)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | aggr c = count() by bin(_time, 10m) | sort -_time | fields _time, c",
+            )
+            .await,
+            "+---------------------+---+\n\
+             | _time               | c |\n\
+             +---------------------+---+\n\
+             | 2026-10-17T12:10:00 | 1 |\n\
+             | 2026-10-17T12:00:00 | 2 |\n\
+             +---------------------+---+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | aggr c = count() by bin(bytes, 100) | sort bytes",
+            )
+            .await,
+            "+-------+---+\n\
+             | bytes | c |\n\
+             +-------+---+\n\
+             | 100.0 | 2 |\n\
+             | 200.0 | 1 |\n\
+             +-------+---+",
+        );
+    }
+
+    #[tokio::test]
+    async fn conversions_turn_invalid_values_into_null() {
+        let data = TestData::new("conversions_turn_invalid_values_into_null");
+        let raw: ArrayRef = Arc::new(StringArray::from(vec!["42", "4.5", "true", "abc"]));
+        let stamp: ArrayRef = Arc::new(StringArray::from(vec![
+            "17/Oct/2026:14:00:00 +0200",
+            "17/Oct/2026:12:30:00 +0000",
+            "2026-10-17",
+            "never",
+        ]));
+        data.write("api", "a", vec![("raw", raw), ("stamp", stamp)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | eval n = to_number(raw), i = raw::int, f = raw::float, \
+                 b = raw::bool, t = stamp::timestamp | fields raw, n, i, f, b, t",
+            )
+            .await,
+            "+------+------+----+------+------+---------------------+\n\
+             | raw  | n    | i  | f    | b    | t                   |\n\
+             +------+------+----+------+------+---------------------+\n\
+             | 42   | 42.0 | 42 | 42.0 |      |                     |\n\
+             | 4.5  | 4.5  |    | 4.5  |      |                     |\n\
+             | true |      |    |      | true | 2026-10-17T00:00:00 |\n\
+             | abc  |      |    |      |      |                     |\n\
+             +------+------+----+------+------+---------------------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api \
+                 | eval t = to_timestamp(stamp, '%d/%b/%Y:%H:%M:%S %z'), \
+                 d = to_timestamp(stamp, '%Y-%m-%d'), s = strftime(t, '%Y-%m-%d %H:%M') \
+                 | fields t, d, s",
+            )
+            .await,
+            "+---------------------+---------------------+------------------+\n\
+             | t                   | d                   | s                |\n\
+             +---------------------+---------------------+------------------+\n\
+             | 2026-10-17T12:00:00 |                     | 2026-10-17 12:00 |\n\
+             | 2026-10-17T12:30:00 |                     | 2026-10-17 12:30 |\n\
+             |                     | 2026-10-17T00:00:00 |                  |\n\
+             |                     |                     |                  |\n\
+             +---------------------+---------------------+------------------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_schema_and_bounds_follow_new_files() {
+        let data = TestData::new("cached_schema_and_bounds_follow_new_files");
+        data.write(
+            "api",
+            "a",
+            vec![("_time", timestamps(&["2026-10-17T12:00Z"]))],
+        );
+        let context = data.context();
+        assert_eq!(
+            execute(
+                &context,
+                "source api from 2026-10-17T11:00Z | aggr n = count()"
+            )
+            .await,
+            "+---+\n| n |\n+---+\n| 1 |\n+---+",
+        );
+
+        let status: ArrayRef = Arc::new(StringArray::from(vec!["ok"]));
+        data.write(
+            "api",
+            "b",
+            vec![
+                ("_time", timestamps(&["2026-10-17T13:00Z"])),
+                ("status", status),
+            ],
+        );
+        assert_eq!(
+            execute(&context, "source api from 2026-10-17T12:30Z").await,
+            "+---------------------+--------+\n\
+             | _time               | status |\n\
+             +---------------------+--------+\n\
+             | 2026-10-17T13:00:00 | ok     |\n\
+             +---------------------+--------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn comparison_with_null() {
+        let data = TestData::new("comparison_with_null");
+        let user: ArrayRef = Arc::new(StringArray::from(vec![Some("ada"), None, Some("bob")]));
+        data.write("api", "a", vec![("user", user)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | where user != null | aggr n = count()"
+            )
+            .await,
+            "+---+\n| n |\n+---+\n| 2 |\n+---+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | where null == user | aggr n = count()"
+            )
+            .await,
+            "+---+\n| n |\n+---+\n| 1 |\n+---+",
+        );
+    }
+
+    #[tokio::test]
+    async fn dedup_keeps_the_first_or_last_rows() {
+        let data = TestData::new("dedup_keeps_the_first_or_last_rows");
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3, 4, 5]));
+        let host: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "a", "a", "b"]));
+        data.write("api", "a", vec![("id", id), ("host", host)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(&context, "source api | dedup host | sort id").await,
+            "+----+------+\n\
+             | id | host |\n\
+             +----+------+\n\
+             | 1  | a    |\n\
+             | 2  | b    |\n\
+             +----+------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | dedup 2 host keepfirst=false | sort id"
+            )
+            .await,
+            "+----+------+\n\
+             | id | host |\n\
+             +----+------+\n\
+             | 2  | b    |\n\
+             | 3  | a    |\n\
+             | 4  | a    |\n\
+             | 5  | b    |\n\
+             +----+------+",
+        );
+        assert_eq!(
+            execute(&context, "source api | dedup host sortby -id | sort id").await,
+            "+----+------+\n\
+             | id | host |\n\
+             +----+------+\n\
+             | 4  | a    |\n\
+             | 5  | b    |\n\
+             +----+------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn dedup_keeps_the_order_of_events() {
+        let data = TestData::new("dedup_keeps_the_order_of_events");
+        let time = timestamps(&[
+            "2026-10-17T12:00Z",
+            "2026-10-17T12:01Z",
+            "2026-10-17T12:02Z",
+            "2026-10-17T12:03Z",
+            "2026-10-17T12:04Z",
+        ]);
+        let host: ArrayRef = Arc::new(StringArray::from(vec!["c", "a", "c", "b", "a"]));
+        data.write("api", "a", vec![("_time", time), ("host", host)]);
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3, 4]));
+        let host: ArrayRef = Arc::new(StringArray::from(vec!["c", "a", "b", "a"]));
+        data.write("worker", "a", vec![("id", id), ("host", host)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(&context, "source api | dedup host").await,
+            "+---------------------+------+\n\
+             | _time               | host |\n\
+             +---------------------+------+\n\
+             | 2026-10-17T12:00:00 | c    |\n\
+             | 2026-10-17T12:01:00 | a    |\n\
+             | 2026-10-17T12:03:00 | b    |\n\
+             +---------------------+------+",
+        );
+        assert_eq!(
+            execute(&context, "source worker | dedup host keepfirst=false").await,
+            "+----+------+\n\
+             | id | host |\n\
+             +----+------+\n\
+             | 1  | c    |\n\
+             | 3  | b    |\n\
+             | 4  | a    |\n\
+             +----+------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn durations_out_of_range_are_errors() {
+        let data = TestData::new("durations_out_of_range_are_errors");
+        data.write(
+            "api",
+            "a",
+            vec![("_time", timestamps(&["2026-10-17T12:00Z"]))],
+        );
+        let context = data.context();
+
+        for source in [
+            "source api since 106752d",
+            "source api since 200000d",
+            "source api | eval t = _time - 200000d",
+            "source api since 1d | timechart span=1h count() | compare 106752d",
+            "source api from 1900-01-01 | timechart span=1d count() | compare 100000d",
+            "source api since 1d | aggr n = count() | compare 60000d | compare 60000d",
+        ] {
+            let error = context.execute(source).await.unwrap_err();
+            assert!(error.to_string().contains("out of range"), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn grok_uses_patterns_of_the_data_directory() {
+        let data = TestData::new("grok_uses_patterns_of_the_data_directory");
+        let message: ArrayRef = Arc::new(StringArray::from(vec![
+            "10.0.0.1 WARN 503 upstream timed out",
+            "10.0.0.2 INFO ok",
+        ]));
+        data.write("api", "a", vec![("message", message)]);
+        let patterns_path = data.path.join(GROK_PATTERNS_DIRECTORY);
+        fs::create_dir_all(&patterns_path).unwrap();
+        fs::write(
+            patterns_path.join("app.grok"),
+            "# Lines of the app.\n\
+             APPLINE %{IP:client} %{LEVEL:level} %{INT:code:int} %{GREEDYDATA:text}\n",
+        )
+        .unwrap();
+        fs::write(patterns_path.join("levels.grok"), "LEVEL [A-Z]+\n").unwrap();
+        // Only `*.grok` files are read.
+        fs::write(patterns_path.join("notes.txt"), "APPLINE .*\n").unwrap();
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | grok message '%{APPLINE}' | fields client, level, code, text",
+            )
+            .await,
+            "+----------+-------+------+--------------------+\n\
+             | client   | level | code | text               |\n\
+             +----------+-------+------+--------------------+\n\
+             | 10.0.0.1 | WARN  | 503  | upstream timed out |\n\
+             |          |       |      |                    |\n\
+             +----------+-------+------+--------------------+",
+        );
+        // Pattern files are read again for every query.
+        fs::write(
+            patterns_path.join("app.grok"),
+            "APPLINE %{IP:client} %{LEVEL:level}(?: %{INT:code:int})? %{GREEDYDATA:text}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            execute(
+                &context,
+                "source api | grok message '%{APPLINE}' | fields client, level, code, text",
+            )
+            .await,
+            "+----------+-------+------+--------------------+\n\
+             | client   | level | code | text               |\n\
+             +----------+-------+------+--------------------+\n\
+             | 10.0.0.1 | WARN  | 503  | upstream timed out |\n\
+             | 10.0.0.2 | INFO  |      | ok                 |\n\
+             +----------+-------+------+--------------------+",
+        );
+        // The directory of the patterns isn't a table.
+        assert_eq!(
+            execute(&context, "source * | aggr n = count()").await,
+            "+---+\n| n |\n+---+\n| 2 |\n+---+",
+        );
+    }
+
+    #[tokio::test]
+    async fn index_counts_from_zero_or_from_the_end() {
+        let data = TestData::new("index_counts_from_zero_or_from_the_end");
+        let tags: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int64Type, _, _>(vec![
+            Some(vec![Some(10), Some(20), Some(30)]),
+        ]));
+        data.write("api", "a", vec![("tags", tags)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | eval first = tags[0], last = tags[-1] | fields first, last",
+            )
+            .await,
+            "+-------+------+\n\
+             | first | last |\n\
+             +-------+------+\n\
+             | 10    | 30   |\n\
+             +-------+------+",
+        );
+        let error = context
+            .execute("source api | eval x = tags[9223372036854775807]")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("out of range"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn kv_extracts_sampled_keys_into_columns() {
+        let data = TestData::new("kv_extracts_sampled_keys_into_columns");
+        let message: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("user=ada status=200"),
+            Some("status=500 latency=12"),
+            None,
+        ]));
+        data.write("api", "a", vec![("message", message)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | kv message | fields user, status, latency"
+            )
+            .await,
+            "+------+--------+---------+\n\
+             | user | status | latency |\n\
+             +------+--------+---------+\n\
+             | ada  | 200    |         |\n\
+             |      | 500    | 12      |\n\
+             |      |        |         |\n\
+             +------+--------+---------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                r#"source api | kv message pairdelim=" " kvdelim="=" prefix=kv_ | fields kv_*"#,
+            )
+            .await,
+            "+---------+-----------+------------+\n\
+             | kv_user | kv_status | kv_latency |\n\
+             +---------+-----------+------------+\n\
+             | ada     | 200       |            |\n\
+             |         | 500       | 12         |\n\
+             |         |           |            |\n\
+             +---------+-----------+------------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn lookup_adds_the_columns_of_matching_rows() {
+        let data = TestData::new("lookup_adds_the_columns_of_matching_rows");
+        let host: ArrayRef = Arc::new(StringArray::from(vec!["A", "b", "c"]));
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3]));
+        data.write("api", "a", vec![("id", id), ("host", host)]);
+        let host: ArrayRef = Arc::new(StringArray::from(vec!["a"]));
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![4]));
+        data.write("worker", "a", vec![("id", id), ("host", host)]);
+        data.write_csv("hosts", "host,region\na,eu\nb,us\n");
+        let context = data.context();
+
+        assert_eq!(
+            execute(&context, "source api | lookup hosts on host | sort id").await,
+            "+----+------+--------+\n\
+             | id | host | region |\n\
+             +----+------+--------+\n\
+             | 1  | A    |        |\n\
+             | 2  | b    | us     |\n\
+             | 3  | c    |        |\n\
+             +----+------+--------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | eval host = lower(host) | lookup hosts on host | sort id",
+            )
+            .await,
+            "+----+------+--------+\n\
+             | id | host | region |\n\
+             +----+------+--------+\n\
+             | 1  | a    | eu     |\n\
+             | 2  | b    | us     |\n\
+             | 3  | c    |        |\n\
+             +----+------+--------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api, worker | lookup hosts on host | sort id"
+            )
+            .await,
+            "+----+------+--------+--------+\n\
+             | id | host | _table | region |\n\
+             +----+------+--------+--------+\n\
+             | 1  | A    | api    |        |\n\
+             | 2  | b    | api    | us     |\n\
+             | 3  | c    | api    |        |\n\
+             | 4  | a    | worker | eu     |\n\
+             +----+------+--------+--------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn join_combines_the_rows_of_a_sub_query() {
+        let data = TestData::new("join_combines_the_rows_of_a_sub_query");
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3]));
+        let user: ArrayRef = Arc::new(StringArray::from(vec!["ada", "bob", "eve"]));
+        let status: ArrayRef = Arc::new(StringArray::from(vec!["ok", "error", "error"]));
+        data.write(
+            "api",
+            "a",
+            vec![("id", id), ("user", user), ("status", status)],
+        );
+        let user: ArrayRef = Arc::new(StringArray::from(vec!["ada", "bob", "max"]));
+        let status: ArrayRef = Arc::new(StringArray::from(vec!["admin", "guest", "admin"]));
+        data.write("users", "a", vec![("user", user), ("status", status)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | join [source users] on user | sort id"
+            )
+            .await,
+            "+----+------+--------+\n\
+             | id | user | status |\n\
+             +----+------+--------+\n\
+             | 1  | ada  | ok     |\n\
+             | 2  | bob  | error  |\n\
+             +----+------+--------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | join type=full [source users] on user | sort user",
+            )
+            .await,
+            "+----+------+--------+\n\
+             | id | user | status |\n\
+             +----+------+--------+\n\
+             | 1  | ada  | ok     |\n\
+             | 2  | bob  | error  |\n\
+             | 3  | eve  | error  |\n\
+             |    | max  |        |\n\
+             +----+------+--------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | join [source api | aggr n = count() by status] on status | sort id",
+            )
+            .await,
+            "+----+------+--------+---+\n\
+             | id | user | status | n |\n\
+             +----+------+--------+---+\n\
+             | 1  | ada  | ok     | 1 |\n\
+             | 2  | bob  | error  | 2 |\n\
+             | 3  | eve  | error  | 2 |\n\
+             +----+------+--------+---+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | eval name = upper(user) \
+                 | join [source users | eval name = upper(user), role = status] on name | sort id",
+            )
+            .await,
+            "+----+------+--------+------+-------+\n\
+             | id | user | status | name | role  |\n\
+             +----+------+--------+------+-------+\n\
+             | 1  | ada  | ok     | ADA  | admin |\n\
+             | 2  | bob  | error  | BOB  | guest |\n\
+             +----+------+--------+------+-------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn parse_extracts_typed_groups() {
+        let data = TestData::new("parse_extracts_typed_groups");
+        let message: ArrayRef = Arc::new(StringArray::from(vec![
+            "GET /index 200",
+            "POST /login abc",
+            "malformed",
+        ]));
+        data.write("api", "a", vec![("message", message)]);
+        let context = data.context();
+
+        // Values that don't match, or can't be cast to the type hint, are null.
+        assert_eq!(
+            execute(
+                &context,
+                r#"source api | parse message r"(?P<method>[A-Z]+) (?P<path>\S+) (?P<status:int>\w+)"
+                   | eval doubled = status * 2 | fields method, path, status, doubled"#,
+            )
+            .await,
+            "+--------+--------+--------+---------+\n\
+             | method | path   | status | doubled |\n\
+             +--------+--------+--------+---------+\n\
+             | GET    | /index | 200    | 400     |\n\
+             | POST   | /login |        |         |\n\
+             |        |        |        |         |\n\
+             +--------+--------+--------+---------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn patterns_clusters_messages_into_templates() {
+        let data = TestData::new("patterns_clusters_messages_into_templates");
+        let time = timestamps(&[
+            "2026-10-17T12:00Z",
+            "2026-10-17T12:01Z",
+            "2026-10-17T12:02Z",
+            "2026-10-17T12:03Z",
+        ]);
+        let message: ArrayRef = Arc::new(StringArray::from(vec![
+            "login succeeded for ada from 10.0.0.1",
+            "login succeeded for bob from 10.0.0.2",
+            "disk full on /var",
+            "login succeeded for eve from 10.0.0.3",
+        ]));
+        data.write("api", "a", vec![("_time", time), ("message", message)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | patterns message | fields template, count"
+            )
+            .await,
+            "+-----------------------------------+-------+\n\
+             | template                          | count |\n\
+             +-----------------------------------+-------+\n\
+             | login succeeded for <*> from <IP> | 3     |\n\
+             | disk full on /var                 | 1     |\n\
+             +-----------------------------------+-------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn sources_are_combined_by_column_name() {
+        let data = TestData::new("sources_are_combined_by_column_name");
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![1, 2]));
+        let status: ArrayRef = Arc::new(StringArray::from(vec!["ok", "error"]));
+        data.write("svc_api", "a", vec![("id", id), ("status", status)]);
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![3]));
+        let queue: ArrayRef = Arc::new(StringArray::from(vec!["jobs"]));
+        data.write("svc_worker", "a", vec![("id", id), ("queue", queue)]);
+        data.write_csv("svc_hosts", "id,host\n1,a\n");
+        let context = data.context();
+
+        let expected = "+----+--------+------------+-------+\n\
+                        | id | status | _table     | queue |\n\
+                        +----+--------+------------+-------+\n\
+                        | 1  | ok     | svc_api    |       |\n\
+                        | 2  | error  | svc_api    |       |\n\
+                        | 3  |        | svc_worker | jobs  |\n\
+                        +----+--------+------------+-------+";
+        assert_eq!(
+            execute(&context, "source svc_api, svc_worker | sort id").await,
+            expected,
+        );
+        // Wildcards only match tables of events, not lookup tables.
+        assert_eq!(execute(&context, "source svc_* | sort id").await, expected);
+    }
+
+    #[tokio::test]
+    async fn eventstats_and_streamstats_add_aggregates_to_every_row() {
+        let data = TestData::new("eventstats_and_streamstats_add_aggregates_to_every_row");
+        let time = timestamps(&[
+            "2026-10-17T12:00Z",
+            "2026-10-17T12:01Z",
+            "2026-10-17T12:02Z",
+            "2026-10-17T12:03Z",
+        ]);
+        let host: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "a", "a"]));
+        let bytes: ArrayRef = Arc::new(Int64Array::from(vec![10, 20, 30, 40]));
+        data.write(
+            "api",
+            "a",
+            vec![("_time", time), ("host", host), ("bytes", bytes)],
+        );
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api | eventstats total = sum(bytes) by host",
+            )
+            .await,
+            "+---------------------+------+-------+-------+\n\
+             | _time               | host | bytes | total |\n\
+             +---------------------+------+-------+-------+\n\
+             | 2026-10-17T12:00:00 | a    | 10    | 80    |\n\
+             | 2026-10-17T12:01:00 | b    | 20    | 20    |\n\
+             | 2026-10-17T12:02:00 | a    | 30    | 80    |\n\
+             | 2026-10-17T12:03:00 | a    | 40    | 80    |\n\
+             +---------------------+------+-------+-------+",
+        );
+        assert_eq!(
+            execute(
+                &context,
+                "source api | streamstats total = sum(bytes) \
+                 | streamstats window=2 recent = sum(bytes) by host",
+            )
+            .await,
+            "+---------------------+------+-------+-------+--------+\n\
+             | _time               | host | bytes | total | recent |\n\
+             +---------------------+------+-------+-------+--------+\n\
+             | 2026-10-17T12:00:00 | a    | 10    | 10    | 10     |\n\
+             | 2026-10-17T12:01:00 | b    | 20    | 30    | 20     |\n\
+             | 2026-10-17T12:02:00 | a    | 30    | 60    | 40     |\n\
+             | 2026-10-17T12:03:00 | a    | 40    | 100   | 70     |\n\
+             +---------------------+------+-------+-------+--------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn streamstats_keeps_time_order() {
+        let data = TestData::new("streamstats_keeps_time_order");
+        let time = timestamps(&[
+            "2026-10-17T12:00Z",
+            "2026-10-17T12:01Z",
+            "2026-10-17T12:02Z",
+            "2026-10-17T12:03Z",
+        ]);
+        let status: ArrayRef = Arc::new(StringArray::from(vec!["ok", "error", "ok", "error"]));
+        data.write("api", "a", vec![("_time", time), ("status", status)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(&context, "source api | streamstats n = count() by status").await,
+            "+---------------------+--------+---+\n\
+             | _time               | status | n |\n\
+             +---------------------+--------+---+\n\
+             | 2026-10-17T12:00:00 | ok     | 1 |\n\
+             | 2026-10-17T12:01:00 | error  | 1 |\n\
+             | 2026-10-17T12:02:00 | ok     | 2 |\n\
+             | 2026-10-17T12:03:00 | error  | 2 |\n\
+             +---------------------+--------+---+",
+        );
+    }
+
+    #[tokio::test]
+    async fn timechart_fills_the_time_range_with_zeros() {
+        let data = TestData::new("timechart_fills_the_time_range_with_zeros");
+        let time = timestamps(&[
+            "2026-10-17T12:01:10Z",
+            "2026-10-17T12:02:20Z",
+            "2026-10-17T12:02:30Z",
+        ]);
+        let status: ArrayRef = Arc::new(StringArray::from(vec!["ok", "ok", "error"]));
+        data.write("api", "a", vec![("_time", time), ("status", status)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api from 2026-10-17T11:59:30Z to 2026-10-17T12:04Z \
+                 | timechart span=1m count(), errors = count() where status == \"error\"",
+            )
+            .await,
+            "+---------------------+-------+--------+\n\
+             | _time               | count | errors |\n\
+             +---------------------+-------+--------+\n\
+             | 2026-10-17T11:59:00 | 0     | 0      |\n\
+             | 2026-10-17T12:00:00 | 0     | 0      |\n\
+             | 2026-10-17T12:01:00 | 1     | 0      |\n\
+             | 2026-10-17T12:02:00 | 2     | 1      |\n\
+             | 2026-10-17T12:03:00 | 0     | 0      |\n\
+             +---------------------+-------+--------+",
+        );
+        // Without a time range, the buckets span the events.
+        assert_eq!(
+            execute(&context, "source api | timechart span=1m count() by status").await,
+            "+---------------------+--------+-------+\n\
+             | _time               | status | count |\n\
+             +---------------------+--------+-------+\n\
+             | 2026-10-17T12:01:00 | error  | 0     |\n\
+             | 2026-10-17T12:01:00 | ok     | 1     |\n\
+             | 2026-10-17T12:02:00 | error  | 1     |\n\
+             | 2026-10-17T12:02:00 | ok     | 1     |\n\
+             +---------------------+--------+-------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn top_and_rare_count_values_with_their_share() {
+        let data = TestData::new("top_and_rare_count_values_with_their_share");
+        let host: ArrayRef = Arc::new(StringArray::from(vec!["a", "a", "a", "a", "b", "b"]));
+        let status: ArrayRef = Arc::new(StringArray::from(vec![
+            "ok", "ok", "error", "warn", "ok", "error",
+        ]));
+        data.write("api", "a", vec![("host", host), ("status", status)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(&context, "source api | top 2 status").await,
+            "+--------+-------+--------------------+\n\
+             | status | count | percent            |\n\
+             +--------+-------+--------------------+\n\
+             | ok     | 3     | 50.0               |\n\
+             | error  | 2     | 33.333333333333336 |\n\
+             +--------+-------+--------------------+",
+        );
+        assert_eq!(
+            execute(&context, "source api | rare 1 status by host").await,
+            "+------+--------+-------+---------+\n\
+             | host | status | count | percent |\n\
+             +------+--------+-------+---------+\n\
+             | a    | error  | 1     | 25.0    |\n\
+             | b    | error  | 1     | 50.0    |\n\
+             +------+--------+-------+---------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn unnest_drops_empty_and_null_lists() {
+        let data = TestData::new("unnest_drops_empty_and_null_lists");
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3]));
+        let tags: ArrayRef = Arc::new(ListArray::from_iter_primitive::<Int64Type, _, _>(vec![
+            Some(vec![Some(10), Some(20)]),
+            Some(vec![]),
+            None,
+        ]));
+        data.write("api", "a", vec![("id", id), ("tags", tags)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(&context, "source api | unnest tags").await,
+            "+----+------+\n\
+             | id | tags |\n\
+             +----+------+\n\
+             | 1  | 10   |\n\
+             | 1  | 20   |\n\
+             +----+------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn mvexpand_flattens_struct_elements() {
+        let data = TestData::new("mvexpand_flattens_struct_elements");
+        let id: ArrayRef = Arc::new(Int64Array::from(vec![1, 2]));
+        data.write("api", "a", vec![("id", id)]);
+        let context = data.context();
+
+        assert_eq!(
+            execute(
+                &context,
+                "source api \
+                 | eval events = make_array(named_struct('code', id, 'kind', 'start'), \
+                                            named_struct('code', id * 10, 'kind', 'stop')) \
+                 | mvexpand events flatten=true | sort code",
+            )
+            .await,
+            "+----+------+-------+\n\
+             | id | code | kind  |\n\
+             +----+------+-------+\n\
+             | 1  | 1    | start |\n\
+             | 2  | 2    | start |\n\
+             | 1  | 10   | stop  |\n\
+             | 2  | 20   | stop  |\n\
+             +----+------+-------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn window_over_text_timestamps_with_offsets() {
+        let data = TestData::new("window_over_text_timestamps_with_offsets");
+        let time: ArrayRef = Arc::new(StringArray::from(vec![
+            "2026-10-17T23:00:00Z",
+            "2026-10-18T01:00:00+05:00",
+        ]));
+        data.write("mixed", "a", vec![("_time", time)]);
+        let context = data.context();
+
+        // The text statistics of the file end at `2026-10-18T01:00:00+05:00`, which is before
+        // the window as text but not as a timestamp.
+        assert_eq!(
+            execute(
+                &context,
+                "source mixed from 2026-10-17T21:00Z | aggr n = count()"
+            )
+            .await,
+            "+---+\n| n |\n+---+\n| 1 |\n+---+",
+        );
+    }
+
+    #[tokio::test]
+    async fn window_outside_of_every_file() {
+        let data = TestData::new("window_outside_of_every_file");
+        data.write(
+            "api",
+            "a",
+            vec![("_time", timestamps(&["2026-10-17T12:00Z"]))],
+        );
+        data.write(
+            "api",
+            "b",
+            vec![("_time", timestamps(&["2026-10-17T13:00Z"]))],
+        );
+        data.write(
+            "worker",
+            "a",
+            vec![("_time", timestamps(&["2026-10-17T12:00Z"]))],
+        );
+        let context = data.context();
+
+        assert_eq!(
+            execute(&context, "source api from 2030-01-01 | aggr n = count()").await,
+            "+---+\n| n |\n+---+\n| 0 |\n+---+",
+        );
+        assert_eq!(
+            execute(&context, "source api to 2020-01-01").await,
+            "++\n++"
+        );
+        assert_eq!(
+            execute(&context, "source api, worker from 2030-01-01").await,
+            "++\n++"
+        );
+        assert_eq!(
+            execute(&context, "source api from 2026-10-17T12:30Z").await,
+            "+---------------------+\n\
+             | _time               |\n\
+             +---------------------+\n\
+             | 2026-10-17T13:00:00 |\n\
+             +---------------------+",
+        );
+    }
 }
@@ -0,0 +1,474 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::{Arc, LazyLock};
+
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, Int64Array, ListArray, StringArray, StructArray,
+    TimestampNanosecondArray,
+};
+use datafusion::arrow::buffer::OffsetBuffer;
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field, Fields, Int64Type, TimestampNanosecondType};
+use datafusion::common::ScalarValue;
+use datafusion::error::Result;
+use datafusion::logical_expr::function::AccumulatorArgs;
+use datafusion::logical_expr::{
+    Accumulator, AggregateUDFImpl, Signature, TypeSignature, Volatility,
+};
+use regex::{Captures, Regex};
+
+use crate::time::TIMESTAMP_DATA_TYPE;
+
+/// Token of a template that differs between the messages grouped into it.
+const WILDCARD: &str = "<*>";
+
+/// Share of its tokens a message must have in common with a template to be grouped into it.
+const SIMILARITY_THRESHOLD: f64 = 0.4;
+
+/// Number of leading tokens a message must share with a template to be grouped into it.
+const PREFIX_LENGTH: usize = 2;
+
+static UUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b")
+        .expect("valid regex")
+});
+static IP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d{1,3}(\.\d{1,3}){3}(:\d+)?\b").expect("valid regex"));
+static HEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(0[xX][0-9a-fA-F]+|[0-9a-fA-F]{8,})\b").expect("valid regex"));
+static NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d+(\.\d+)?").expect("valid regex"));
+
+/// Groups messages into templates the way Drain does: variable parts like numbers, IPs, UUIDs
+/// and hex strings are masked, and messages with the same number of tokens and the same
+/// leading tokens are grouped if enough of their tokens are equal. Tokens that differ within
+/// a group become `<*>`. Takes the message and optionally its timestamp, and returns a list of
+/// templates with their count, a sample message and when they were first and last seen.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Drain {
+    signature: Signature,
+}
+
+impl Drain {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::one_of(
+                vec![TypeSignature::Any(1), TypeSignature::Any(2)],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl AggregateUDFImpl for Drain {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "drain"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::new_list(
+            DataType::Struct(template_fields()),
+            true,
+        ))
+    }
+
+    fn accumulator(&self, _acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(DrainAccumulator::default()))
+    }
+}
+
+fn template_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("template", DataType::Utf8, false),
+        Field::new("count", DataType::Int64, false),
+        Field::new("sample", DataType::Utf8, false),
+        Field::new("first_seen", TIMESTAMP_DATA_TYPE, true),
+        Field::new("last_seen", TIMESTAMP_DATA_TYPE, true),
+    ])
+}
+
+#[derive(Debug, Clone)]
+struct Template {
+    tokens: Vec<String>,
+    count: i64,
+    sample: String,
+    first_seen: Option<i64>,
+    last_seen: Option<i64>,
+}
+
+impl Template {
+    fn similarity(&self, tokens: &[String]) -> f64 {
+        if tokens.is_empty() {
+            return 1.0;
+        }
+        let equal = self
+            .tokens
+            .iter()
+            .zip(tokens)
+            .filter(|(token, other)| *token != WILDCARD && token == other)
+            .count();
+        equal as f64 / tokens.len() as f64
+    }
+
+    fn merge(&mut self, other: Template) {
+        for (token, other) in self.tokens.iter_mut().zip(other.tokens) {
+            if *token != other {
+                *token = WILDCARD.to_owned();
+            }
+        }
+        self.count += other.count;
+        self.first_seen = min_option(self.first_seen, other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+    }
+}
+
+/// Templates grouped by their number of tokens and their leading tokens, in which tokens with
+/// digits left after masking stand for any token.
+#[derive(Debug, Default)]
+struct DrainAccumulator {
+    groups: HashMap<(usize, Vec<String>), Vec<Template>>,
+}
+
+impl DrainAccumulator {
+    fn add(&mut self, template: Template) {
+        let prefix = template
+            .tokens
+            .iter()
+            .take(PREFIX_LENGTH)
+            .map(|token| {
+                if token.bytes().any(|byte| byte.is_ascii_digit()) {
+                    WILDCARD.to_owned()
+                } else {
+                    token.clone()
+                }
+            })
+            .collect();
+        let group = self
+            .groups
+            .entry((template.tokens.len(), prefix))
+            .or_default();
+        let best = group
+            .iter_mut()
+            .map(|existing| (existing.similarity(&template.tokens), existing))
+            .filter(|(similarity, _)| *similarity >= SIMILARITY_THRESHOLD)
+            .max_by(|(a, _), (b, _)| a.total_cmp(b));
+        match best {
+            Some((_, existing)) => existing.merge(template),
+            None => group.push(template),
+        }
+    }
+
+    /// Returns the templates as a list, the most frequent first.
+    fn to_list(&self) -> ScalarValue {
+        let mut templates: Vec<&Template> = self.groups.values().flatten().collect();
+        templates.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tokens.cmp(&b.tokens)));
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(
+                templates.iter().map(|template| template.tokens.join(" ")),
+            )),
+            Arc::new(Int64Array::from_iter_values(
+                templates.iter().map(|template| template.count),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                templates.iter().map(|template| template.sample.as_str()),
+            )),
+            Arc::new(TimestampNanosecondArray::from_iter(
+                templates.iter().map(|template| template.first_seen),
+            )),
+            Arc::new(TimestampNanosecondArray::from_iter(
+                templates.iter().map(|template| template.last_seen),
+            )),
+        ];
+        let fields = template_fields();
+        let values = StructArray::new(fields.clone(), columns, None);
+        let list = ListArray::new(
+            Arc::new(Field::new_list_field(DataType::Struct(fields), true)),
+            OffsetBuffer::from_lengths([values.len()]),
+            Arc::new(values),
+            None,
+        );
+        ScalarValue::List(Arc::new(list))
+    }
+}
+
+impl Accumulator for DrainAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let messages = cast(&values[0], &DataType::Utf8)?;
+        let timestamps = match values.get(1) {
+            Some(timestamps) => Some(cast(timestamps, &TIMESTAMP_DATA_TYPE)?),
+            None => None,
+        };
+        let timestamps = timestamps
+            .as_ref()
+            .map(|timestamps| timestamps.as_primitive::<TimestampNanosecondType>());
+
+        for (row, message) in messages.as_string::<i32>().iter().enumerate() {
+            let Some(message) = message else {
+                continue;
+            };
+            let timestamp = timestamps
+                .filter(|timestamps| timestamps.is_valid(row))
+                .map(|timestamps| timestamps.value(row));
+            self.add(Template {
+                tokens: mask(message)
+                    .split_whitespace()
+                    .map(str::to_owned)
+                    .collect(),
+                count: 1,
+                sample: message.to_owned(),
+                first_seen: timestamp,
+                last_seen: timestamp,
+            });
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(self.to_list())
+    }
+
+    fn size(&self) -> usize {
+        size_of::<Self>()
+            + self
+                .groups
+                .iter()
+                .flat_map(|((_, prefix), templates)| {
+                    prefix
+                        .iter()
+                        .map(String::len)
+                        .chain(templates.iter().map(|template| {
+                            size_of::<Template>()
+                                + template.sample.len()
+                                + template.tokens.iter().map(String::len).sum::<usize>()
+                        }))
+                })
+                .sum::<usize>()
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![self.to_list()])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let lists = states[0].as_list::<i32>();
+        for row in 0..lists.len() {
+            if lists.is_null(row) {
+                continue;
+            }
+            let values = lists.value(row);
+            let values = values.as_struct();
+            let templates = values.column(0).as_string::<i32>();
+            let counts = values.column(1).as_primitive::<Int64Type>();
+            let samples = values.column(2).as_string::<i32>();
+            let first_seen = values.column(3).as_primitive::<TimestampNanosecondType>();
+            let last_seen = values.column(4).as_primitive::<TimestampNanosecondType>();
+            for index in 0..values.len() {
+                self.add(Template {
+                    tokens: templates
+                        .value(index)
+                        .split_whitespace()
+                        .map(str::to_owned)
+                        .collect(),
+                    count: counts.value(index),
+                    sample: samples.value(index).to_owned(),
+                    first_seen: first_seen.is_valid(index).then(|| first_seen.value(index)),
+                    last_seen: last_seen.is_valid(index).then(|| last_seen.value(index)),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the variable parts of a message with placeholders.
+fn mask(message: &str) -> String {
+    let message = UUID.replace_all(message, "<UUID>");
+    let message = IP.replace_all(&message, "<IP>");
+    // Long runs of hex digits are only masked if they aren't plain words or numbers.
+    let message = HEX.replace_all(&message, |captures: &Captures| {
+        let hex = &captures[0];
+        let has_digit = hex.bytes().any(|byte| byte.is_ascii_digit());
+        let has_letter = hex.bytes().any(|byte| byte.is_ascii_alphabetic());
+        if hex.starts_with("0x") || hex.starts_with("0X") || (has_digit && has_letter) {
+            "<HEX>".to_owned()
+        } else {
+            hex.to_owned()
+        }
+    });
+    NUMBER.replace_all(&message, "<NUM>").into_owned()
+}
+
+fn min_option(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A template as returned by the accumulator.
+    #[derive(Debug, PartialEq)]
+    struct Row {
+        template: String,
+        count: i64,
+        sample: String,
+        first_seen: Option<i64>,
+        last_seen: Option<i64>,
+    }
+
+    fn update(accumulator: &mut DrainAccumulator, messages: &[&str], timestamps: &[i64]) {
+        let mut values: Vec<ArrayRef> = vec![Arc::new(StringArray::from(messages.to_vec()))];
+        if !timestamps.is_empty() {
+            values.push(Arc::new(TimestampNanosecondArray::from(
+                timestamps.to_vec(),
+            )));
+        }
+        accumulator.update_batch(&values).unwrap();
+    }
+
+    fn rows(accumulator: &mut DrainAccumulator) -> Vec<Row> {
+        let ScalarValue::List(list) = accumulator.evaluate().unwrap() else {
+            panic!("drain returns a list");
+        };
+        let values = list.value(0);
+        let values = values.as_struct();
+        let templates = values.column(0).as_string::<i32>();
+        let counts = values.column(1).as_primitive::<Int64Type>();
+        let samples = values.column(2).as_string::<i32>();
+        let first_seen = values.column(3).as_primitive::<TimestampNanosecondType>();
+        let last_seen = values.column(4).as_primitive::<TimestampNanosecondType>();
+        (0..values.len())
+            .map(|index| Row {
+                template: templates.value(index).to_owned(),
+                count: counts.value(index),
+                sample: samples.value(index).to_owned(),
+                first_seen: first_seen.is_valid(index).then(|| first_seen.value(index)),
+                last_seen: last_seen.is_valid(index).then(|| last_seen.value(index)),
+            })
+            .collect()
+    }
+
+    fn templates(accumulator: &mut DrainAccumulator) -> Vec<(String, i64)> {
+        rows(accumulator)
+            .into_iter()
+            .map(|row| (row.template, row.count))
+            .collect()
+    }
+
+    #[test]
+    fn masks_variable_parts() {
+        assert_eq!(
+            mask("user 42 took 1.5s from 10.0.0.1:8080"),
+            "user <NUM> took <NUM>s from <IP>",
+        );
+        assert_eq!(
+            mask("request 550e8400-e29b-41d4-a716-446655440000 failed"),
+            "request <UUID> failed",
+        );
+        assert_eq!(mask("commit deadbeef12 at 0x1F"), "commit <HEX> at <HEX>");
+        // Long plain words and numbers aren't hex.
+        assert_eq!(mask("abcdefgh 12345678"), "abcdefgh <NUM>");
+    }
+
+    #[test]
+    fn groups_similar_messages() {
+        let mut accumulator = DrainAccumulator::default();
+        update(
+            &mut accumulator,
+            &[
+                "connection to alice closed after 5ms",
+                "connection to bob closed after 7ms",
+                "connection to alice closed after 9ms",
+                "disk full",
+            ],
+            &[],
+        );
+        assert_eq!(
+            templates(&mut accumulator),
+            vec![
+                ("connection to <*> closed after <NUM>ms".to_owned(), 3),
+                ("disk full".to_owned(), 1),
+            ],
+        );
+    }
+
+    #[test]
+    fn separates_by_length_prefix_and_similarity() {
+        let mut accumulator = DrainAccumulator::default();
+        update(
+            &mut accumulator,
+            &[
+                "session opened for alice",
+                "session opened for alice now",
+                "session closed for alice",
+                "a b c d e f",
+                "a b u v w x",
+            ],
+            &[],
+        );
+        assert_eq!(templates(&mut accumulator).len(), 5);
+    }
+
+    #[test]
+    fn keeps_the_first_sample_and_the_time_range() {
+        let mut accumulator = DrainAccumulator::default();
+        update(
+            &mut accumulator,
+            &["job 1 done", "job 2 done", "job 3 done"],
+            &[20, 10, 30],
+        );
+        assert_eq!(
+            rows(&mut accumulator),
+            vec![Row {
+                template: "job <NUM> done".to_owned(),
+                count: 3,
+                sample: "job 1 done".to_owned(),
+                first_seen: Some(10),
+                last_seen: Some(30),
+            }],
+        );
+    }
+
+    #[test]
+    fn merges_partial_states() {
+        let mut first = DrainAccumulator::default();
+        update(&mut first, &["GET /users took 5ms", "disk full"], &[1, 2]);
+        let mut second = DrainAccumulator::default();
+        update(&mut second, &["GET /users failed 7ms"], &[3]);
+
+        let state = second.state().unwrap()[0].to_array().unwrap();
+        first.merge_batch(&[state]).unwrap();
+        assert_eq!(
+            rows(&mut first),
+            vec![
+                Row {
+                    template: "GET /users <*> <NUM>ms".to_owned(),
+                    count: 2,
+                    sample: "GET /users took 5ms".to_owned(),
+                    first_seen: Some(1),
+                    last_seen: Some(3),
+                },
+                Row {
+                    template: "disk full".to_owned(),
+                    count: 1,
+                    sample: "disk full".to_owned(),
+                    first_seen: Some(2),
+                    last_seen: Some(2),
+                },
+            ],
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, AsArray, StringBuilder, StructArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field, Fields};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDFImpl, Signature, Volatility,
+};
+
+/// Extracts the values of a fixed set of keys from `key=value` pairs in a string into a struct
+/// with one string field per key. Fields are null if their key doesn't occur.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) struct KeyValueExtract {
+    pair_delimiter: String,
+    kv_delimiter: String,
+    keys: Vec<String>,
+    signature: Signature,
+}
+
+impl KeyValueExtract {
+    pub fn new(pair_delimiter: &str, kv_delimiter: &str, keys: Vec<String>) -> Self {
+        Self {
+            pair_delimiter: pair_delimiter.to_owned(),
+            kv_delimiter: kv_delimiter.to_owned(),
+            keys,
+            signature: Signature::any(1, Volatility::Immutable),
+        }
+    }
+
+    fn fields(&self) -> Fields {
+        self.keys
+            .iter()
+            .map(|key| Field::new(key, DataType::Utf8, true))
+            .collect()
+    }
+}
+
+impl ScalarUDFImpl for KeyValueExtract {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "key_value_extract"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Struct(self.fields()))
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        let [value] = ColumnarValue::values_to_arrays(&args.args)?
+            .try_into()
+            .map_err(|_| {
+                DataFusionError::Plan("key_value_extract expects one argument".to_owned())
+            })?;
+        let value = cast(&value, &DataType::Utf8)?;
+        let value = value.as_string::<i32>();
+
+        let mut builders: Vec<StringBuilder> =
+            self.keys.iter().map(|_| StringBuilder::new()).collect();
+        for row in value.iter() {
+            let pairs = row
+                .map(|row| split_pairs(row, &self.pair_delimiter, &self.kv_delimiter))
+                .unwrap_or_default();
+            for (key, builder) in self.keys.iter().zip(&mut builders) {
+                let value = pairs.iter().find(|(pair_key, _)| pair_key == key);
+                builder.append_option(value.map(|(_, value)| *value));
+            }
+        }
+
+        let columns: Vec<ArrayRef> = builders
+            .into_iter()
+            .map(|mut builder| Arc::new(builder.finish()) as ArrayRef)
+            .collect();
+        let result = StructArray::try_new(self.fields(), columns, None)?;
+        Ok(ColumnarValue::Array(Arc::new(result)))
+    }
+}
+
+/// Splits text into key-value pairs. Pair delimiters within double quotes don't split, and the
+/// quotes around a value are removed. Pieces without a key are skipped. Both delimiters must
+/// not be empty.
+pub(crate) fn split_pairs<'a>(
+    text: &'a str,
+    pair_delimiter: &str,
+    kv_delimiter: &str,
+) -> Vec<(&'a str, &'a str)> {
+    let mut pairs = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (piece, next) = match find_unquoted(rest, pair_delimiter) {
+            Some(index) => (&rest[..index], &rest[index + pair_delimiter.len()..]),
+            None => (rest, ""),
+        };
+        rest = next;
+        if let Some((key, value)) = piece.split_once(kv_delimiter)
+            && !key.is_empty()
+        {
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            pairs.push((key, value));
+        }
+    }
+    pairs
+}
+
+fn find_unquoted(text: &str, delimiter: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (index, character) in text.char_indices() {
+        if character == '"' {
+            quoted = !quoted;
+        } else if !quoted && text[index..].starts_with(delimiter) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_pairs() {
+        assert_eq!(
+            split_pairs("a=1 b=2", " ", "="),
+            vec![("a", "1"), ("b", "2")]
+        );
+        assert_eq!(split_pairs("", " ", "="), vec![]);
+    }
+
+    #[test]
+    fn quoted_values_keep_delimiters() {
+        assert_eq!(
+            split_pairs(r#"msg="a b=c" level=info"#, " ", "="),
+            vec![("msg", "a b=c"), ("level", "info")],
+        );
+        // An unterminated quote extends to the end of the text and is kept.
+        assert_eq!(
+            split_pairs(r#"msg="a b=c"#, " ", "="),
+            vec![("msg", r#""a b=c"#)]
+        );
+    }
+
+    #[test]
+    fn skips_pieces_without_key() {
+        assert_eq!(
+            split_pairs("=1 flag  b=2 c=", " ", "="),
+            vec![("b", "2"), ("c", "")],
+        );
+    }
+
+    #[test]
+    fn splits_on_the_first_kv_delimiter() {
+        assert_eq!(split_pairs("url=/?q=1", " ", "="), vec![("url", "/?q=1")]);
+    }
+
+    #[test]
+    fn multi_character_delimiters() {
+        assert_eq!(
+            split_pairs("a: 1; b: \"x; y\"", "; ", ": "),
+            vec![("a", "1"), ("b", "x; y")],
+        );
+    }
+}
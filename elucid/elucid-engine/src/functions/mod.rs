@@ -0,0 +1,9 @@
+mod drain;
+mod key_value_extract;
+mod parse_timestamp;
+mod regex_extract;
+
+pub(crate) use drain::Drain;
+pub(crate) use key_value_extract::{KeyValueExtract, split_pairs};
+pub(crate) use parse_timestamp::ParseTimestamp;
+pub(crate) use regex_extract::RegexExtract;
@@ -0,0 +1,119 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use datafusion::arrow::array::{AsArray, TimestampNanosecondArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDFImpl, Signature, Volatility,
+};
+
+use crate::time::TIMESTAMP_DATA_TYPE;
+
+/// Parses strings into timestamps with a strftime-like format, as in
+/// `to_timestamp(time, "%d/%b/%Y:%H:%M:%S %z")`. Values that don't match the format become
+/// null. Formats without an offset are taken to be in UTC, and formats without a time to be
+/// at midnight.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) struct ParseTimestamp {
+    signature: Signature,
+}
+
+impl ParseTimestamp {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::any(2, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ParseTimestamp {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "parse_timestamp"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(TIMESTAMP_DATA_TYPE)
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        let [value, format] = ColumnarValue::values_to_arrays(&args.args)?
+            .try_into()
+            .map_err(|_| {
+                DataFusionError::Plan("parse_timestamp expects a value and a format".to_owned())
+            })?;
+        let value = cast(&value, &DataType::Utf8)?;
+        let format = cast(&format, &DataType::Utf8)?;
+
+        let timestamps: TimestampNanosecondArray = value
+            .as_string::<i32>()
+            .iter()
+            .zip(format.as_string::<i32>().iter())
+            .map(|(value, format)| parse(value?, format?))
+            .collect();
+        Ok(ColumnarValue::Array(Arc::new(timestamps)))
+    }
+}
+
+fn parse(value: &str, format: &str) -> Option<i64> {
+    let timestamp = DateTime::parse_from_str(value, format)
+        .map(|timestamp| timestamp.to_utc())
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, format).map(|timestamp| timestamp.and_utc())
+        })
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, format).map(|date| {
+                date.and_hms_opt(0, 0, 0)
+                    .expect("midnight is valid")
+                    .and_utc()
+            })
+        })
+        .ok()?;
+    timestamp.timestamp_nanos_opt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::parse_timestamp;
+
+    #[test]
+    fn parses_timestamps_with_an_offset() {
+        assert_eq!(
+            parse("17/Oct/2026:14:00:00 +0200", "%d/%b/%Y:%H:%M:%S %z"),
+            Some(parse_timestamp("2026-10-17T12:00:00Z").unwrap()),
+        );
+    }
+
+    #[test]
+    fn timestamps_without_an_offset_are_in_utc() {
+        assert_eq!(
+            parse("2026-10-17 12:30:15", "%Y-%m-%d %H:%M:%S"),
+            Some(parse_timestamp("2026-10-17T12:30:15Z").unwrap()),
+        );
+    }
+
+    #[test]
+    fn dates_are_at_midnight() {
+        assert_eq!(
+            parse("17.10.2026", "%d.%m.%Y"),
+            Some(parse_timestamp("2026-10-17").unwrap()),
+        );
+    }
+
+    #[test]
+    fn values_that_do_not_match_are_none() {
+        assert_eq!(parse("yesterday", "%Y-%m-%d"), None);
+        assert_eq!(parse("2026-10-17", "%Y-%m-%d %H:%M:%S"), None);
+    }
+}
@@ -0,0 +1,246 @@
+use std::any::Any;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, LazyLock};
+
+use datafusion::arrow::array::{ArrayRef, AsArray, StringBuilder, StructArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field, Fields};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDFImpl, Signature, Volatility,
+};
+use regex::Regex;
+
+use crate::time::TIMESTAMP_DATA_TYPE;
+
+/// Matches named groups carrying a type hint, such as `(?P<status:int>` or `(?<status:int>`.
+static TYPED_GROUP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\(\?P?<([A-Za-z_][A-Za-z0-9_]*):([A-Za-z]+)>").expect("valid regex")
+});
+
+/// Extracts the named capture groups of a regular expression from a string into a struct with
+/// one string field per group. Fields are null if the value doesn't match.
+#[derive(Debug)]
+pub(crate) struct RegexExtract {
+    regex: Regex,
+    /// Named groups in the order they appear in the pattern, with their type hint if any.
+    groups: Vec<(String, Option<DataType>)>,
+    signature: Signature,
+}
+
+impl RegexExtract {
+    /// Compiles the pattern. Named groups may carry a type hint after a colon, as in
+    /// `(?P<status:int>\d+)`, which is one of `int`, `float`, `bool`, `string` or `timestamp`.
+    pub fn try_new(pattern: &str) -> Result<Self> {
+        let mut hints = Vec::new();
+        for captures in TYPED_GROUP.captures_iter(pattern) {
+            let data_type = match &captures[2] {
+                "int" => DataType::Int64,
+                "float" => DataType::Float64,
+                "bool" => DataType::Boolean,
+                "string" => DataType::Utf8,
+                "timestamp" => TIMESTAMP_DATA_TYPE,
+                other => {
+                    return Err(DataFusionError::Plan(format!(
+                        "Unknown type '{}' for capture group '{}'",
+                        other, &captures[1],
+                    )));
+                }
+            };
+            hints.push((captures[1].to_owned(), data_type));
+        }
+        let pattern = TYPED_GROUP.replace_all(pattern, "(?P<$1>");
+
+        let regex = Regex::new(&pattern).map_err(|error| {
+            DataFusionError::Plan(format!("Invalid regular expression: {}", error))
+        })?;
+        let groups: Vec<_> = regex
+            .capture_names()
+            .flatten()
+            .map(|name| {
+                let data_type = hints
+                    .iter()
+                    .find(|(hint_name, _)| hint_name == name)
+                    .map(|(_, data_type)| data_type.clone());
+                (name.to_owned(), data_type)
+            })
+            .collect();
+        if groups.is_empty() {
+            return Err(DataFusionError::Plan(
+                "Regular expression has no named capture groups".to_owned(),
+            ));
+        }
+
+        Ok(Self {
+            regex,
+            groups,
+            signature: Signature::any(1, Volatility::Immutable),
+        })
+    }
+
+    pub fn groups(&self) -> &[(String, Option<DataType>)] {
+        &self.groups
+    }
+
+    fn fields(&self) -> Fields {
+        self.groups
+            .iter()
+            .map(|(name, _)| Field::new(name, DataType::Utf8, true))
+            .collect()
+    }
+}
+
+impl PartialEq for RegexExtract {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+    }
+}
+
+impl Eq for RegexExtract {}
+
+impl Hash for RegexExtract {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.regex.as_str().hash(state);
+    }
+}
+
+impl ScalarUDFImpl for RegexExtract {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "regex_extract"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Struct(self.fields()))
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        let [value] = ColumnarValue::values_to_arrays(&args.args)?
+            .try_into()
+            .map_err(|_| DataFusionError::Plan("regex_extract expects one argument".to_owned()))?;
+        let value = cast(&value, &DataType::Utf8)?;
+        let value = value.as_string::<i32>();
+
+        let mut builders: Vec<StringBuilder> =
+            self.groups.iter().map(|_| StringBuilder::new()).collect();
+        for row in value.iter() {
+            let captures = row.and_then(|row| self.regex.captures(row));
+            for ((name, _), builder) in self.groups.iter().zip(&mut builders) {
+                let capture = captures.as_ref().and_then(|captures| captures.name(name));
+                builder.append_option(capture.map(|capture| capture.as_str()));
+            }
+        }
+
+        let columns: Vec<ArrayRef> = builders
+            .into_iter()
+            .map(|mut builder| Arc::new(builder.finish()) as ArrayRef)
+            .collect();
+        let result = StructArray::try_new(self.fields(), columns, None)?;
+        Ok(ColumnarValue::Array(Arc::new(result)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Array, StringArray};
+    use datafusion::config::ConfigOptions;
+
+    use super::*;
+
+    fn extract(pattern: &str, values: Vec<Option<&str>>) -> StructArray {
+        let extract = RegexExtract::try_new(pattern).unwrap();
+        let number_rows = values.len();
+        let args = ScalarFunctionArgs {
+            args: vec![ColumnarValue::Array(Arc::new(StringArray::from(values)))],
+            arg_fields: vec![Arc::new(Field::new("value", DataType::Utf8, true))],
+            number_rows,
+            return_field: Arc::new(Field::new("", extract.return_type(&[]).unwrap(), true)),
+            config_options: Arc::new(ConfigOptions::default()),
+        };
+        let ColumnarValue::Array(result) = extract.invoke_with_args(args).unwrap() else {
+            panic!("regex_extract returns an array");
+        };
+        result.as_struct().clone()
+    }
+
+    fn strings(array: &StructArray, name: &str) -> Vec<Option<String>> {
+        let column = array.column_by_name(name).unwrap().as_string::<i32>();
+        column
+            .iter()
+            .map(|value| value.map(str::to_owned))
+            .collect()
+    }
+
+    #[test]
+    fn extracts_named_groups() {
+        let result = extract(
+            r"(?P<method>[A-Z]+) (?P<path>\S+)",
+            vec![Some("GET /index"), Some("POST /login")],
+        );
+        assert_eq!(
+            strings(&result, "method"),
+            vec![Some("GET".to_owned()), Some("POST".to_owned())],
+        );
+        assert_eq!(
+            strings(&result, "path"),
+            vec![Some("/index".to_owned()), Some("/login".to_owned())],
+        );
+    }
+
+    #[test]
+    fn rows_that_do_not_match_are_null() {
+        let result = extract(
+            r"status=(?P<status>\d+)(?: (?P<reason>\w+))?",
+            vec![Some("status=200"), Some("no status"), None],
+        );
+        assert_eq!(
+            strings(&result, "status"),
+            vec![Some("200".to_owned()), None, None]
+        );
+        // An optional group that doesn't take part in the match is null as well.
+        assert_eq!(strings(&result, "reason"), vec![None, None, None]);
+        assert_eq!(result.null_count(), 0);
+    }
+
+    #[test]
+    fn type_hints_are_stripped_from_the_pattern() {
+        let extract =
+            RegexExtract::try_new(r"(?P<status:int>\d+) (?<took:float>\S+) (?P<path>\S+)").unwrap();
+        assert_eq!(
+            extract.groups(),
+            [
+                ("status".to_owned(), Some(DataType::Int64)),
+                ("took".to_owned(), Some(DataType::Float64)),
+                ("path".to_owned(), None),
+            ],
+        );
+        assert_eq!(
+            extract.regex.as_str(),
+            r"(?P<status>\d+) (?P<took>\S+) (?P<path>\S+)"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_types_and_patterns_without_named_groups() {
+        let error = RegexExtract::try_new(r"(?P<status:number>\d+)").unwrap_err();
+        assert!(
+            error.to_string().contains("Unknown type 'number'"),
+            "{}",
+            error
+        );
+        let error = RegexExtract::try_new(r"(\d+) (\w+)").unwrap_err();
+        assert!(
+            error.to_string().contains("no named capture groups"),
+            "{}",
+            error
+        );
+        assert!(RegexExtract::try_new(r"(?P<status>\d+").is_err());
+    }
+}
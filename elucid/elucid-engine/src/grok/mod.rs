@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use datafusion::error::{DataFusionError, Result};
+use regex::{Captures, Regex};
+
+/// Definitions shipped with the engine, in the same format as user-defined pattern files.
+const BUILTIN_DEFINITIONS: &str = include_str!("builtin.grok");
+
+/// Nesting depth at which pattern references are assumed to be cyclic.
+const MAX_DEPTH: usize = 32;
+
+/// Matches `%{NAME}`, `%{NAME:field}` and `%{NAME:field:type}`.
+static REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"%\{([A-Za-z0-9_]+)(?::([A-Za-z_][A-Za-z0-9_]*))?(?::([A-Za-z]+))?\}")
+        .expect("valid regex")
+});
+
+/// Named grok patterns, compiled into regular expressions for the `parse` command.
+#[derive(Debug, Clone)]
+pub(crate) struct GrokPatterns {
+    definitions: HashMap<String, String>,
+}
+
+impl Default for GrokPatterns {
+    fn default() -> Self {
+        let mut patterns = Self {
+            definitions: HashMap::new(),
+        };
+        patterns
+            .add_definitions(BUILTIN_DEFINITIONS)
+            .expect("valid built-in grok patterns");
+        patterns
+    }
+}
+
+impl GrokPatterns {
+    /// Adds the definitions of a pattern file, replacing existing patterns of the same name.
+    /// Every non-empty line that isn't a `#` comment defines a pattern as `NAME regex`.
+    pub fn add_definitions(&mut self, source: &str) -> Result<()> {
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, definition)) = line.split_once(char::is_whitespace) else {
+                return Err(DataFusionError::Plan(format!(
+                    "Grok pattern on line {} has no definition",
+                    index + 1,
+                )));
+            };
+            self.definitions
+                .insert(name.to_owned(), definition.trim_start().to_owned());
+        }
+        Ok(())
+    }
+
+    /// Expands all pattern references into a regular expression. References with a field
+    /// become named capture groups, keeping the type hint for `RegexExtract`.
+    pub fn compile(&self, pattern: &str) -> Result<String> {
+        self.expand(pattern, 0)
+    }
+
+    fn expand(&self, pattern: &str, depth: usize) -> Result<String> {
+        if depth > MAX_DEPTH {
+            return Err(DataFusionError::Plan(
+                "Grok patterns are nested too deeply, they probably reference each other"
+                    .to_owned(),
+            ));
+        }
+
+        let mut expanded = String::with_capacity(pattern.len());
+        let mut last_end = 0;
+        for captures in REFERENCE.captures_iter(pattern) {
+            let reference = captures.get(0).expect("whole match");
+            expanded.push_str(&pattern[last_end..reference.start()]);
+            expanded.push_str(&self.expand_reference(&captures, depth)?);
+            last_end = reference.end();
+        }
+        expanded.push_str(&pattern[last_end..]);
+        Ok(expanded)
+    }
+
+    fn expand_reference(&self, captures: &Captures, depth: usize) -> Result<String> {
+        let name = &captures[1];
+        let definition = self
+            .definitions
+            .get(name)
+            .ok_or_else(|| DataFusionError::Plan(format!("Unknown grok pattern '{}'", name)))?;
+        let definition = self.expand(definition, depth + 1)?;
+        Ok(match (captures.get(2), captures.get(3)) {
+            (Some(field), Some(data_type)) => format!(
+                "(?P<{}:{}>{})",
+                field.as_str(),
+                data_type.as_str(),
+                definition,
+            ),
+            (Some(field), None) => format!("(?P<{}>{})", field.as_str(), definition),
+            (None, _) => format!("(?:{})", definition),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::RegexExtract;
+
+    fn patterns(definitions: &str) -> GrokPatterns {
+        let mut patterns = GrokPatterns {
+            definitions: HashMap::new(),
+        };
+        patterns.add_definitions(definitions).unwrap();
+        patterns
+    }
+
+    #[test]
+    fn expands_nested_references() {
+        let patterns = patterns(
+            "# Comments and blank lines are skipped.\n\
+             \n\
+             DIGITS \\d+\n\
+             PAIR %{DIGITS}-%{DIGITS}\n",
+        );
+        assert_eq!(
+            patterns.compile("id=%{PAIR}").unwrap(),
+            r"id=(?:(?:\d+)-(?:\d+))",
+        );
+    }
+
+    #[test]
+    fn references_with_fields_become_capture_groups() {
+        let patterns = patterns("DIGITS \\d+");
+        assert_eq!(
+            patterns
+                .compile("%{DIGITS:status:int} %{DIGITS:bytes}")
+                .unwrap(),
+            r"(?P<status:int>\d+) (?P<bytes>\d+)",
+        );
+    }
+
+    #[test]
+    fn later_definitions_replace_earlier_ones() {
+        let mut patterns = patterns("WORD \\w+");
+        patterns.add_definitions("WORD [a-z]+").unwrap();
+        assert_eq!(patterns.compile("%{WORD}").unwrap(), "(?:[a-z]+)");
+    }
+
+    #[test]
+    fn unknown_references_are_rejected() {
+        let error = patterns("").compile("%{MISSING}").unwrap_err();
+        assert!(error.to_string().contains("Unknown grok pattern 'MISSING'"));
+    }
+
+    #[test]
+    fn cyclic_references_are_rejected() {
+        let error = patterns("A x%{B}\nB y%{A}").compile("%{A}").unwrap_err();
+        assert!(error.to_string().contains("nested too deeply"));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        // Patterns `P0` to `P{length - 1}`, each referencing the next but the last.
+        let chain = |length: usize| {
+            let definitions: Vec<String> = (0..length)
+                .map(|level| match level + 1 {
+                    next if next < length => format!("P{} %{{P{}}}", level, next),
+                    _ => format!("P{} end", level),
+                })
+                .collect();
+            patterns(&definitions.join("\n"))
+        };
+        assert!(chain(MAX_DEPTH).compile("%{P0}").is_ok());
+        assert!(chain(MAX_DEPTH + 1).compile("%{P0}").is_err());
+    }
+
+    #[test]
+    fn builtin_patterns_compile() {
+        let patterns = GrokPatterns::default();
+        for name in patterns.definitions.keys() {
+            let compiled = patterns.compile(&format!("%{{{}:value}}", name)).unwrap();
+            assert!(
+                RegexExtract::try_new(&compiled).is_ok(),
+                "invalid pattern {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn definitions_need_a_regex() {
+        let mut patterns = patterns("");
+        let error = patterns.add_definitions("OK .*\nBROKEN").unwrap_err();
+        assert!(error.to_string().contains("line 2 has no definition"));
+    }
+}
@@ -1,4 +1,8 @@
 mod context;
+mod functions;
+mod grok;
 mod planner;
+mod time;
+mod wildcard;
 
 pub use context::Context;
@@ -1,69 +1,231 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use datafusion::common::ScalarValue::Null;
+use datafusion::arrow::array::AsArray;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::ScalarValue::{self, Null};
+use datafusion::common::{Column, NullEquality, UnnestOptions};
 use datafusion::datasource::DefaultTableSource;
 use datafusion::error::{DataFusionError, Result};
 use datafusion::execution::FunctionRegistry;
-use datafusion::logical_expr::expr::{AggregateFunction, ScalarFunction};
-use datafusion::logical_expr::{BinaryExpr, LogicalPlan, LogicalPlanBuilder, Operator, SortExpr};
+use datafusion::functions_aggregate::expr_fn::{count, max, min};
+use datafusion::functions_aggregate::sum::sum_udaf;
+use datafusion::functions_window::expr_fn::row_number;
+use datafusion::logical_expr::expr::{AggregateFunction, ScalarFunction, WindowFunction};
+use datafusion::logical_expr::expr_rewriter::normalize_col;
+use datafusion::logical_expr::{
+    BinaryExpr, Case, ExprFunctionExt, ExprSchemable, JoinType, Like, LogicalPlan,
+    LogicalPlanBuilder, Operator, ScalarUDF, SortExpr, WindowFrame, WindowFrameBound,
+    WindowFrameUnits,
+};
 use datafusion::prelude::*;
-use elucid_language::{BinaryOperator, Command, Expression, Query, SortOrder};
+use elucid_language::{
+    BinaryOperator, CastType, Command, Expression, FieldsMode, JoinKind, Query, SortExpression,
+    SortOrder, TimeRange, UnaryOperator,
+};
+
+use crate::functions::{split_pairs, KeyValueExtract, ParseTimestamp, RegexExtract};
+use crate::grok::GrokPatterns;
+use crate::time::{self, TimeWindow, TIMESTAMP_DATA_TYPE};
+use crate::wildcard;
+
+const DEFAULT_FREQUENCY_LIMIT: i64 = 10;
+
+const DEFAULT_TIMECHART_SPAN: Duration = Duration::from_secs(60);
+
+const DEFAULT_FILL_VALUE: i64 = 0;
+
+const DEFAULT_PAIR_DELIMITER: &str = " ";
+
+const DEFAULT_KV_DELIMITER: &str = "=";
+
+/// Number of rows `kv` inspects to discover the keys.
+const KV_SAMPLE_ROWS: usize = 1_000;
+
+/// Column holding the table each row comes from when reading several tables.
+const TABLE_COLUMN: &str = "_table";
+
+/// Group column of an aggregation.
+#[derive(Debug, Clone)]
+struct GroupKey {
+    name: String,
+    /// Whether the column holds time derived from the timestamp column, like a time bucket.
+    is_time: bool,
+}
 
 pub struct QueryPlanner<'a> {
     context: &'a SessionContext,
+    timestamp_column: &'a str,
+    /// Moment relative time bounds are resolved against, fixed for the lifetime of the planner.
+    now: i64,
+    grok_patterns: GrokPatterns,
+    /// Names of all tables, which wildcards in `source` are matched against.
+    tables: Vec<String>,
 }
 
 impl<'a> QueryPlanner<'a> {
-    pub fn new(ctx: &'a SessionContext) -> Self {
-        Self { context: ctx }
+    pub fn new(ctx: &'a SessionContext, timestamp_column: &'a str) -> Self {
+        Self {
+            context: ctx,
+            timestamp_column,
+            now: time::now_nanos(),
+            grok_patterns: GrokPatterns::default(),
+            tables: Vec::new(),
+        }
+    }
+
+    /// Sets the names of the tables that wildcards in `source` are matched against.
+    pub fn with_tables(mut self, tables: Vec<String>) -> Self {
+        self.tables = tables;
+        self
+    }
+
+    /// Expands source patterns into table names, ordered by the first pattern that matches
+    /// them. Names without wildcards are taken as they are.
+    pub fn resolve_sources(&self, sources: &[String]) -> Result<Vec<String>> {
+        let mut table_names: Vec<String> = Vec::new();
+        for source in sources {
+            let matching: Vec<&String> = if wildcard::is_wildcard(source) {
+                self.tables
+                    .iter()
+                    .filter(|table_name| wildcard::matches(source, table_name))
+                    .collect()
+            } else {
+                vec![source]
+            };
+            if matching.is_empty() {
+                return Err(DataFusionError::Plan(format!(
+                    "No table matches '{}'",
+                    source
+                )));
+            }
+            for table_name in matching {
+                if !table_names.contains(table_name) {
+                    table_names.push(table_name.clone());
+                }
+            }
+        }
+        Ok(table_names)
+    }
+
+    /// Sets the patterns available to `grok`. Defaults to the built-in patterns.
+    pub fn with_grok_patterns(mut self, grok_patterns: GrokPatterns) -> Self {
+        self.grok_patterns = grok_patterns;
+        self
+    }
+
+    /// Resolves the time range moved back in time by `shift` nanoseconds, as read by `compare`.
+    pub fn time_window(&self, time_range: &TimeRange, shift: i64) -> Result<TimeWindow> {
+        TimeWindow::resolve(time_range, self.now)?.shift(shift, self.now)
     }
 
     pub async fn create_logical_plan(&self, query: Query) -> Result<LogicalPlan> {
+        self.plan_query(query, 0).await?.build()
+    }
+
+    /// Plans the query with its time range moved back in time by `shift` nanoseconds.
+    async fn plan_query(&self, query: Query, shift: i64) -> Result<LogicalPlanBuilder> {
+        let table_names = self.resolve_sources(&query.sources)?;
+        let has_wildcard = query
+            .sources
+            .iter()
+            .any(|source| wildcard::is_wildcard(source));
+        let mut builder = match table_names.as_slice() {
+            [table_name] if !has_wildcard => self.scan(table_name).await?,
+            _ => self.scan_union(&table_names).await?,
+        };
+        let window = query
+            .time_range
+            .as_ref()
+            .map(|time_range| self.time_window(time_range, shift))
+            .transpose()?;
+        if let Some(window) = &window
+            && let Some(predicate) = window.predicate(self.timestamp_expression(&builder)?)
+        {
+            builder = builder.filter(predicate)?;
+        }
+        // Groups of the last aggregation, which `compare` joins the shifted results on.
+        let mut groups = None;
+        for (index, command) in query.commands.iter().enumerate() {
+            builder = match command {
+                Command::Compare(offsets) => {
+                    let keys = groups.as_deref().ok_or_else(|| {
+                        DataFusionError::Plan(
+                            "'compare' must follow an aggregation like 'aggr' or 'timechart'"
+                                .to_owned(),
+                        )
+                    })?;
+                    let previous = Query {
+                        sources: query.sources.clone(),
+                        time_range: query.time_range.clone(),
+                        commands: query.commands[..index].to_vec(),
+                    };
+                    self.plan_compare(builder, previous, keys, offsets, shift)
+                        .await?
+                }
+                command => {
+                    let builder = self.apply_command(builder, command.clone(), window).await?;
+                    if let Some(keys) = self.group_keys(command, &builder)? {
+                        groups = Some(keys);
+                    }
+                    builder
+                }
+            };
+        }
+        Ok(builder)
+    }
+
+    async fn scan(&self, table_name: &str) -> Result<LogicalPlanBuilder> {
         let table_provider = self
             .context
-            .table_provider(&query.source)
+            .table_provider(table_name)
             .await
             .map_err(|error| {
-                DataFusionError::Plan(format!("Table '{}' not found: {}", query.source, error))
+                DataFusionError::Plan(format!("Table '{}' not found: {}", table_name, error))
             })?;
         let table_source = DefaultTableSource::new(table_provider);
+        LogicalPlanBuilder::scan(table_name, Arc::new(table_source), None)
+    }
 
-        let mut builder = LogicalPlanBuilder::scan(&query.source, Arc::new(table_source), None)?;
-        for command in query.commands {
-            builder = self.apply_command(builder, command)?;
+    /// Combines the rows of the tables by column name, filling in nulls for columns a table
+    /// lacks, and adds a column with the name of the table each row comes from.
+    async fn scan_union(&self, table_names: &[String]) -> Result<LogicalPlanBuilder> {
+        let mut union: Option<LogicalPlanBuilder> = None;
+        for table_name in table_names {
+            let scan = self.scan(table_name).await?;
+            let table_column = lit(table_name.as_str()).alias(TABLE_COLUMN);
+            let scan = self
+                .project_columns(scan, vec![(TABLE_COLUMN.to_owned(), table_column)])?
+                .build()?;
+            union = Some(match union {
+                Some(union) => union.union_by_name(scan)?,
+                None => LogicalPlanBuilder::from(scan),
+            });
         }
-        builder.build()
+        union.ok_or_else(|| DataFusionError::Plan("No table to read".to_owned()))
     }
 
-    fn apply_command(
+    /// Applies a command to the pipeline. `window` is the time range of the query, if any.
+    async fn apply_command(
         &self,
         builder: LogicalPlanBuilder,
         command: Command,
+        window: Option<TimeWindow>,
     ) -> Result<LogicalPlanBuilder> {
         match command {
             Command::Where(expression) => {
-                let expression = self.map_expression(expression)?;
+                let expression = self.map_predicate(&builder, expression)?;
                 builder.filter(expression)
             }
             Command::Sort(sort_expressions) => {
-                let sort_expressions: Vec<SortExpr> = sort_expressions
-                    .into_iter()
-                    .map(|sort_expression| {
-                        let expression = self.map_expression(sort_expression.expression)?;
-                        let ascending = match sort_expression.order {
-                            SortOrder::Ascending => true,
-                            SortOrder::Descending => false,
-                        };
-                        Ok(expression.sort(ascending, false))
-                    })
-                    .collect::<Result<_>>()?;
-                builder.sort(sort_expressions)
+                builder.sort(self.map_sort_expressions(sort_expressions)?)
             }
             Command::Limit(n) => builder.limit(0, Some(n as usize)),
             Command::Aggregate { aggregates, by } => {
                 let group_expressions: Vec<Expr> = by
                     .into_iter()
-                    .map(|expression| self.map_expression(expression))
+                    .map(|expression| self.map_group(expression))
                     .collect::<Result<_>>()?;
 
                 let mut aggregate_expressions = Vec::new();
@@ -77,22 +239,1028 @@ impl<'a> QueryPlanner<'a> {
 
                 builder.aggregate(group_expressions, aggregate_expressions)
             }
+            Command::Fields { mode, patterns } => {
+                let expressions = self.resolve_fields(&builder, mode, &patterns)?;
+                builder.project(expressions)
+            }
+            Command::Eval(assignments) => {
+                // Each assignment is projected separately so later ones can reference earlier ones.
+                let mut builder = builder;
+                for (name, expression) in assignments {
+                    let expression = self.map_expression(expression)?.alias(&name);
+                    builder = self.project_columns(builder, vec![(name, expression)])?;
+                }
+                Ok(builder)
+            }
+            Command::Rename(renamings) => {
+                let mut builder = builder;
+                for (old_name, new_name) in renamings {
+                    builder = self.rename_column(builder, &old_name, &new_name)?;
+                }
+                Ok(builder)
+            }
+            Command::Timechart {
+                span,
+                aggregates,
+                by,
+            } => {
+                let span = span.unwrap_or(DEFAULT_TIMECHART_SPAN);
+                self.plan_timechart(builder, span, aggregates, by, window)
+            }
+            Command::EventStats { aggregates, by } => {
+                let frame = WindowFrame::new(None);
+                let builder =
+                    self.plan_window_aggregates(builder, aggregates, by, Vec::new(), frame)?;
+                self.sort_by_time(builder)
+            }
+            Command::StreamStats {
+                window,
+                aggregates,
+                by,
+            } => {
+                let start = match window {
+                    Some(window) if window < 1 => {
+                        return Err(DataFusionError::Plan(
+                            "The window of 'streamstats' must hold at least one row".to_owned(),
+                        ));
+                    }
+                    Some(window) => ScalarValue::UInt64(Some(window as u64 - 1)),
+                    None => ScalarValue::UInt64(None),
+                };
+                let frame = WindowFrame::new_bounds(
+                    WindowFrameUnits::Rows,
+                    WindowFrameBound::Preceding(start),
+                    WindowFrameBound::CurrentRow,
+                );
+                let order = match self.timestamp_expression(&builder) {
+                    Ok(timestamp) => vec![timestamp.sort(true, false)],
+                    Err(_) => Vec::new(),
+                };
+                let builder = self.plan_window_aggregates(builder, aggregates, by, order, frame)?;
+                self.sort_by_time(builder)
+            }
+            Command::Top { limit, fields, by } => {
+                self.plan_frequency(builder, limit, fields, by, false)
+            }
+            Command::Rare { limit, fields, by } => {
+                self.plan_frequency(builder, limit, fields, by, true)
+            }
+            Command::Dedup {
+                count,
+                fields,
+                keep_first,
+                sort,
+            } => self.plan_dedup(builder, count.unwrap_or(1), fields, keep_first, sort),
+            Command::Parse { field, pattern } => self.plan_parse(builder, field, &pattern),
+            Command::Grok { field, pattern } => {
+                let pattern = self.grok_patterns.compile(&pattern)?;
+                self.plan_parse(builder, field, &pattern)
+            }
+            Command::KeyValue {
+                field,
+                pair_delimiter,
+                kv_delimiter,
+                prefix,
+            } => {
+                let pair_delimiter = pair_delimiter.as_deref().unwrap_or(DEFAULT_PAIR_DELIMITER);
+                let kv_delimiter = kv_delimiter.as_deref().unwrap_or(DEFAULT_KV_DELIMITER);
+                let prefix = prefix.as_deref().unwrap_or_default();
+                self.plan_key_value(builder, field, pair_delimiter, kv_delimiter, prefix)
+                    .await
+            }
+            Command::FillNull { value, patterns } => {
+                let value = match value {
+                    Some(value) => self.map_expression(value)?,
+                    None => lit(DEFAULT_FILL_VALUE),
+                };
+                // Without patterns every field is filled, except those no value fits into.
+                let mode = if patterns.is_empty() {
+                    FieldsMode::Exclude
+                } else {
+                    FieldsMode::Include
+                };
+                let schema = builder.schema().clone();
+                let value_type = value.get_type(&schema)?;
+                let mut columns = Vec::new();
+                for field in self.resolve_fields(&builder, mode, &patterns)? {
+                    let Expr::Column(column) = field else {
+                        continue;
+                    };
+                    let data_type = schema.qualified_field_from_column(&column)?.1.data_type();
+                    let compatible = data_type == &value_type
+                        || (data_type.is_numeric() && value_type.is_numeric())
+                        || (is_text(data_type) && is_text(&value_type));
+                    if patterns.is_empty() && !compatible {
+                        continue;
+                    }
+                    let name = column.name.clone();
+                    let mut expression = Expr::Column(column);
+                    // A text value turns the field into text rather than being parsed as its type.
+                    let fill = if !compatible && is_text(&value_type) {
+                        expression = cast(expression, value_type.clone());
+                        value.clone()
+                    } else {
+                        cast(value.clone(), data_type.clone())
+                    };
+                    let expression = coalesce(vec![expression, fill]);
+                    columns.push((name.clone(), expression.alias(name)));
+                }
+                self.project_columns(builder, columns)
+            }
+            Command::Compare(_) => Err(DataFusionError::Plan(
+                "'compare' is planned along with the pipeline before it".to_owned(),
+            )),
+            Command::Patterns { field } => self.plan_patterns(builder, field),
+            Command::Unnest { field, flatten } => self.plan_unnest(builder, &field, flatten),
+            Command::Lookup { table, fields } => {
+                let keys = fields
+                    .iter()
+                    .map(|field| (ident(field), ident(field)))
+                    .collect();
+                let right = self.scan(&table).await?.build()?;
+                self.plan_join(builder, right, JoinType::Left, keys)
+            }
+            Command::Join { kind, query, on } => {
+                let join_type = match kind {
+                    JoinKind::Inner => JoinType::Inner,
+                    JoinKind::Left => JoinType::Left,
+                    JoinKind::Right => JoinType::Right,
+                    JoinKind::Full => JoinType::Full,
+                };
+                let keys = self.join_keys(on)?;
+                let right = Box::pin(self.create_logical_plan(*query)).await?;
+                self.plan_join(builder, right, join_type, keys)
+            }
+        }
+    }
+
+    /// Renames the column `old_name`, dropping any other column already named `new_name`.
+    fn rename_column(
+        &self,
+        builder: LogicalPlanBuilder,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<LogicalPlanBuilder> {
+        let columns = builder.schema().columns();
+        if !columns.iter().any(|column| column.name == old_name) {
+            return Err(DataFusionError::Plan(format!(
+                "Field '{}' not found",
+                old_name,
+            )));
+        }
+        let expressions: Vec<Expr> = columns
+            .into_iter()
+            .filter(|column| column.name == old_name || column.name != new_name)
+            .map(|column| {
+                if column.name == old_name {
+                    Expr::Column(column).alias(new_name)
+                } else {
+                    Expr::Column(column)
+                }
+            })
+            .collect();
+        builder.project(expressions)
+    }
+
+    /// Projects all existing columns, replacing those named like one of the given columns with
+    /// its expression and appending the expressions that have no such column.
+    fn project_columns(
+        &self,
+        builder: LogicalPlanBuilder,
+        mut columns: Vec<(String, Expr)>,
+    ) -> Result<LogicalPlanBuilder> {
+        let mut expressions: Vec<Expr> = builder
+            .schema()
+            .columns()
+            .into_iter()
+            .map(
+                |column| match columns.iter().position(|(name, _)| *name == column.name) {
+                    Some(index) => columns.remove(index).1,
+                    None => Expr::Column(column),
+                },
+            )
+            .collect();
+        expressions.extend(columns.into_iter().map(|(_, expression)| expression));
+        builder.project(expressions)
+    }
+
+    /// Adds a column for every named capture group of the pattern, cast to its type hint.
+    fn plan_parse(
+        &self,
+        builder: LogicalPlanBuilder,
+        field: Expression,
+        pattern: &str,
+    ) -> Result<LogicalPlanBuilder> {
+        let extract = RegexExtract::try_new(pattern)?;
+        let groups = extract.groups().to_vec();
+        // Every group reads its field of the same call, which common subexpression elimination
+        // evaluates once, so the regex runs once per row however many groups it has.
+        let extracted = ScalarUDF::new_from_impl(extract).call(vec![self.map_expression(field)?]);
+        let columns = groups
+            .into_iter()
+            .map(|(name, data_type)| {
+                let mut expression = get_field(extracted.clone(), name.as_str());
+                if let Some(data_type) = data_type {
+                    expression = try_cast(expression, data_type);
+                }
+                (name.clone(), expression.alias(name))
+            })
+            .collect();
+        self.project_columns(builder, columns)
+    }
+
+    /// Joins the results of the `previous` pipeline over windows shifted back by each offset
+    /// onto its current results, by the `groups` of its last aggregation. Shifted values are
+    /// suffixed with the offset, as in `count_1d`, and numeric ones get a difference column
+    /// like `count_delta_1d`. Groups holding time are moved forward to line up.
+    async fn plan_compare(
+        &self,
+        mut builder: LogicalPlanBuilder,
+        previous: Query,
+        groups: &[GroupKey],
+        offsets: &[Duration],
+        shift: i64,
+    ) -> Result<LogicalPlanBuilder> {
+        if previous.time_range.is_none() {
+            return Err(DataFusionError::Plan(
+                "'compare' needs a time range on 'source'".to_owned(),
+            ));
+        }
+        let keys: Vec<String> = groups.iter().map(|group| group.name.clone()).collect();
+        let columns = builder.schema().columns();
+        for key in &keys {
+            if !columns.iter().any(|column| column.name == *key) {
+                return Err(DataFusionError::Plan(format!(
+                    "Group '{}' of the aggregation not found before 'compare'",
+                    key,
+                )));
+            }
+        }
+        let mut numeric_values = Vec::new();
+        for column in &columns {
+            let (_, field) = builder.schema().qualified_field_from_column(column)?;
+            if !keys.contains(&column.name) && field.data_type().is_numeric() {
+                numeric_values.push(column.name.clone());
+            }
+        }
+
+        for offset in offsets {
+            let suffix = time::duration_label(*offset);
+            let shifted =
+                Box::pin(self.plan_query(previous.clone(), time::add_duration(shift, *offset)?))
+                    .await?;
+            let interval = self.map_expression(Expression::Duration(*offset))?;
+            let expressions: Vec<Expr> = shifted
+                .schema()
+                .columns()
+                .into_iter()
+                .map(|column| {
+                    // Keys are renamed, as the names are ambiguous if only one side has them
+                    // qualified by a table.
+                    let name = column.name.clone();
+                    let is_time = groups
+                        .iter()
+                        .any(|group| group.name == name && group.is_time);
+                    if is_time {
+                        (Expr::Column(column) + interval.clone())
+                            .alias(format!("__compare_{}", name))
+                    } else if keys.contains(&name) {
+                        Expr::Column(column).alias(format!("__compare_{}", name))
+                    } else {
+                        Expr::Column(column).alias(format!("{}_{}", name, suffix))
+                    }
+                })
+                .collect();
+            let shifted = shifted.project(expressions)?.build()?;
+
+            let join_keys = keys
+                .iter()
+                .map(|key| (ident(key), ident(format!("__compare_{}", key))))
+                .collect();
+            builder = self.plan_join(builder, shifted, JoinType::Left, join_keys)?;
+            let expressions: Vec<Expr> = builder
+                .schema()
+                .columns()
+                .into_iter()
+                .filter(|column| !column.name.starts_with("__compare_"))
+                .map(Expr::Column)
+                .collect();
+            builder = builder.project(expressions)?;
+            let deltas = numeric_values
+                .iter()
+                .map(|name| {
+                    let delta = format!("{}_delta_{}", name, suffix);
+                    let expression = ident(name) - ident(format!("{}_{}", name, suffix));
+                    (delta.clone(), expression.alias(delta))
+                })
+                .collect();
+            builder = self.project_columns(builder, deltas)?;
+        }
+        // Joins don't keep the order, so rows are sorted by their groups, time first.
+        if keys.is_empty() {
+            return Ok(builder);
+        }
+        builder.sort(keys.iter().map(|key| ident(key).sort(true, false)))
+    }
+
+    /// Returns the group columns of an aggregation command, read from the plan it produced,
+    /// which lists them first. Returns `None` for other commands.
+    fn group_keys(
+        &self,
+        command: &Command,
+        builder: &LogicalPlanBuilder,
+    ) -> Result<Option<Vec<GroupKey>>> {
+        // `timechart` groups by the time bucket ahead of its groups.
+        let (by, bucket_count) = match command {
+            Command::Aggregate { by, .. } => (by, 0),
+            Command::Timechart { by, .. } => (by, 1),
+            _ => return Ok(None),
+        };
+        let schema = builder.schema();
+        let mut keys = Vec::with_capacity(bucket_count + by.len());
+        for index in 0..bucket_count + by.len() {
+            let field = schema.field(index);
+            let is_time = match index.checked_sub(bucket_count) {
+                None => true,
+                Some(index) => {
+                    let expression = self.map_expression(by[index].clone())?;
+                    let uses_timestamp = expression
+                        .column_refs()
+                        .iter()
+                        .any(|column| column.name == self.timestamp_column);
+                    uses_timestamp && matches!(field.data_type(), DataType::Timestamp(_, _))
+                }
+            };
+            keys.push(GroupKey {
+                name: field.name().clone(),
+                is_time,
+            });
+        }
+        Ok(Some(keys))
+    }
+
+    /// Groups the values of the field into templates with the `drain` aggregate, most frequent
+    /// first.
+    fn plan_patterns(
+        &self,
+        builder: LogicalPlanBuilder,
+        field: Expression,
+    ) -> Result<LogicalPlanBuilder> {
+        let drain = self.context.udaf("drain")?;
+        let mut arguments = vec![self.map_expression(field)?];
+        arguments.extend(self.timestamp_expression(&builder).ok());
+        let builder = builder.aggregate(
+            Vec::<Expr>::new(),
+            vec![drain.call(arguments).alias("__patterns")],
+        )?;
+        self.plan_unnest(builder, "__patterns", true)?.sort(vec![
+            ident("count").sort(false, false),
+            ident("template").sort(true, false),
+        ])
+    }
+
+    /// Emits a row per list element. Rows whose list is empty or null are dropped alike.
+    fn plan_unnest(
+        &self,
+        builder: LogicalPlanBuilder,
+        field: &str,
+        flatten: bool,
+    ) -> Result<LogicalPlanBuilder> {
+        let (qualifier, list_field) = builder
+            .schema()
+            .qualified_field_with_unqualified_name(field)
+            .map_err(|_| DataFusionError::Plan(format!("Field '{}' not found", field)))?;
+        let element_type = match list_field.data_type() {
+            DataType::List(element)
+            | DataType::LargeList(element)
+            | DataType::FixedSizeList(element, _) => element.data_type().clone(),
+            data_type => {
+                return Err(DataFusionError::Plan(format!(
+                    "Field '{}' is not a list but {}",
+                    field, data_type,
+                )));
+            }
+        };
+        let column = Column::new(qualifier.cloned(), field);
+        let builder = builder
+            .unnest_column_with_options(column, UnnestOptions::new().with_preserve_nulls(false))?;
+        if !flatten {
+            return Ok(builder);
+        }
+
+        let DataType::Struct(struct_fields) = element_type else {
+            return Err(DataFusionError::Plan(format!(
+                "Elements of '{}' are not structs, so they can't be flattened",
+                field,
+            )));
+        };
+        // The struct fields take the place of the struct, replacing columns of the same name.
+        let mut expressions = Vec::new();
+        for column in builder.schema().columns() {
+            if column.name == field {
+                expressions.extend(struct_fields.iter().map(|struct_field| {
+                    get_field(Expr::Column(column.clone()), struct_field.name().as_str())
+                        .alias(struct_field.name())
+                }));
+            } else if struct_fields.find(&column.name).is_none() {
+                expressions.push(Expr::Column(column));
+            }
+        }
+        builder.project(expressions)
+    }
+
+    /// Adds a column for every key found in `field`. As the schema must be known when planning,
+    /// the keys are discovered by running the plan so far on a sample of rows first. Like with
+    /// `parse`, a column named like an extracted one is replaced, which a `prefix` avoids.
+    async fn plan_key_value(
+        &self,
+        builder: LogicalPlanBuilder,
+        field: Expression,
+        pair_delimiter: &str,
+        kv_delimiter: &str,
+        prefix: &str,
+    ) -> Result<LogicalPlanBuilder> {
+        if pair_delimiter.is_empty() || kv_delimiter.is_empty() {
+            return Err(DataFusionError::Plan(
+                "Delimiters of 'kv' must not be empty".to_owned(),
+            ));
+        }
+        let value = self.map_expression(field)?;
+
+        let sample = builder
+            .clone()
+            .project(vec![cast(value.clone(), DataType::Utf8)])?
+            .limit(0, Some(KV_SAMPLE_ROWS))?
+            .build()?;
+        let batches = self
+            .context
+            .execute_logical_plan(sample)
+            .await?
+            .collect()
+            .await?;
+        let mut keys: Vec<String> = Vec::new();
+        for batch in &batches {
+            for row in batch.column(0).as_string::<i32>().iter().flatten() {
+                for (key, _) in split_pairs(row, pair_delimiter, kv_delimiter) {
+                    if !keys.iter().any(|existing| existing == key) {
+                        keys.push(key.to_owned());
+                    }
+                }
+            }
+        }
+        if keys.is_empty() {
+            return Ok(builder);
+        }
+
+        let extract = KeyValueExtract::new(pair_delimiter, kv_delimiter, keys.clone());
+        // All keys are fields of one call, so each value is split into pairs only once.
+        let extracted = ScalarUDF::new_from_impl(extract).call(vec![value]);
+        let columns = keys
+            .into_iter()
+            .map(|key| {
+                let name = format!("{}{}", prefix, key);
+                let expression = get_field(extracted.clone(), key).alias(&name);
+                (name, expression)
+            })
+            .collect();
+        self.project_columns(builder, columns)
+    }
+
+    /// Joins on pairs of key expressions, the first of each evaluated on this pipeline and the
+    /// second on `right`. Columns of `right` named like a column of this pipeline are dropped,
+    /// except that keys shared by name are merged for right and full joins.
+    fn plan_join(
+        &self,
+        builder: LogicalPlanBuilder,
+        right: LogicalPlan,
+        join_type: JoinType,
+        keys: Vec<(Expr, Expr)>,
+    ) -> Result<LogicalPlanBuilder> {
+        let merged_keys: Vec<String> = match join_type {
+            JoinType::Right | JoinType::Full => keys
+                .iter()
+                .filter_map(|key| match key {
+                    (Expr::Column(left), Expr::Column(right)) if left.name == right.name => {
+                        Some(left.name.clone())
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        // Each side gets its own qualifier, so that columns named alike on both sides, as when a
+        // table is joined with itself, can be told apart.
+        let left = builder.alias("__left")?.build()?;
+        let right = LogicalPlanBuilder::from(right).alias("__right")?.build()?;
+        let left_columns = left.schema().columns();
+        let right_columns = right.schema().columns();
+
+        let mut output: Vec<Expr> = Vec::new();
+        for column in &left_columns {
+            let right_column = right_columns.iter().find(|right| right.name == column.name);
+            let expression = match right_column {
+                Some(right_column) if merged_keys.contains(&column.name) => coalesce(vec![
+                    Expr::Column(column.clone()),
+                    Expr::Column(right_column.clone()),
+                ]),
+                _ => Expr::Column(column.clone()),
+            };
+            output.push(expression.alias(&column.name));
+        }
+        for column in right_columns {
+            if !left_columns.iter().any(|left| left.name == column.name) {
+                let name = column.name.clone();
+                output.push(Expr::Column(column).alias(name));
+            }
+        }
+
+        let mut left_keys = Vec::with_capacity(keys.len());
+        let mut right_keys = Vec::with_capacity(keys.len());
+        for (left_key, right_key) in keys {
+            left_keys.push(normalize_col(left_key, &left)?);
+            right_keys.push(normalize_col(right_key, &right)?);
+        }
+        LogicalPlanBuilder::from(left)
+            .join_with_expr_keys(right, join_type, (left_keys, right_keys), None)?
+            .project(output)
+    }
+
+    /// Splits a join condition into pairs of key expressions for the left and the right side.
+    fn join_keys(&self, on: Expression) -> Result<Vec<(Expr, Expr)>> {
+        match on {
+            Expression::Binary(BinaryOperator::And, left, right) => {
+                let mut keys = self.join_keys(*left)?;
+                keys.extend(self.join_keys(*right)?);
+                Ok(keys)
+            }
+            Expression::Binary(BinaryOperator::Equal, left, right) => Ok(vec![(
+                self.map_expression(*left)?,
+                self.map_expression(*right)?,
+            )]),
+            Expression::Field(name) => Ok(vec![(ident(&name), ident(&name))]),
+            _ => Err(DataFusionError::Plan(
+                "Join condition must combine equalities or field names with 'and'".to_owned(),
+            )),
+        }
+    }
+
+    /// Aggregates into time buckets of `span`, one row per bucket and group, sorted by time.
+    /// Buckets without events are filled in with zeros across the time `window`, or between the
+    /// first and the last event where the window is unbounded. An open end of a bounded window
+    /// stands for `now`. Aggregates without an alias are named after their function.
+    fn plan_timechart(
+        &self,
+        builder: LogicalPlanBuilder,
+        span: Duration,
+        aggregates: Vec<(Expression, Option<String>)>,
+        by: Vec<Expression>,
+        window: Option<TimeWindow>,
+    ) -> Result<LogicalPlanBuilder> {
+        let span = self.map_expression(Expression::Duration(span))?;
+        let bucket = time::bin(span.clone(), self.timestamp_expression(&builder)?)
+            .alias(self.timestamp_column);
+
+        let mut group_expressions = vec![bucket];
+        for expression in by {
+            group_expressions.push(self.map_group(expression)?);
+        }
+        let group_count = group_expressions.len();
+        let aggregate_expressions = aggregates
+            .into_iter()
+            .map(|(expression, alias_option)| {
+                let alias = alias_option.or_else(|| default_aggregate_name(&expression));
+                let expression = self.map_expression(expression)?;
+                Ok(match alias {
+                    Some(alias) => expression.alias(alias),
+                    None => expression,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let aggregated = builder
+            .aggregate(group_expressions, aggregate_expressions)?
+            .alias("aggregated")?
+            .build()?;
+
+        let columns = aggregated.schema().columns();
+        let (group_columns, aggregate_columns) = columns.split_at(group_count);
+        let (time_column, by_columns) = group_columns.split_first().expect("bucket is grouped");
+
+        // Every bucket of the window, crossed with every group. The end of the window is
+        // exclusive, so its last bucket is the one of the moment before.
+        let time_name = time_column.name.as_str();
+        let start = match window.and_then(|window| window.start) {
+            Some(start) => time::bin(span.clone(), time::timestamp_literal(start)),
+            None => col("start"),
+        };
+        let end = match window.map(|window| window.end) {
+            Some(Some(end)) => time::bin(span.clone(), time::timestamp_literal(end - 1)),
+            Some(None) => greatest(vec![
+                col("end"),
+                time::bin(span.clone(), time::timestamp_literal(self.now)),
+            ]),
+            None => col("end"),
+        };
+        let mut grid = LogicalPlanBuilder::from(aggregated.clone())
+            .aggregate(
+                Vec::<Expr>::new(),
+                vec![
+                    min(Expr::Column(time_column.clone())).alias("start"),
+                    max(Expr::Column(time_column.clone())).alias("end"),
+                ],
+            )?
+            .project(vec![gen_series(start, end, span).alias(time_name)])?
+            .unnest_column_with_options(
+                time_name,
+                UnnestOptions::new().with_preserve_nulls(false),
+            )?;
+        if !by_columns.is_empty() {
+            let groups = LogicalPlanBuilder::from(aggregated.clone())
+                .project(by_columns.iter().cloned().map(Expr::Column))?
+                .distinct()?
+                .build()?;
+            grid = grid.cross_join(groups)?;
+        }
+        let grid = grid.alias("grid")?;
+
+        let grid_columns: Vec<Column> = group_columns
+            .iter()
+            .map(|column| Column::new(Some("grid"), &column.name))
+            .collect();
+        let mut expressions: Vec<Expr> = grid_columns
+            .iter()
+            .map(|column| Expr::Column(column.clone()).alias(&column.name))
+            .collect();
+        for column in aggregate_columns {
+            let (_, field) = aggregated.schema().qualified_field_from_column(column)?;
+            let expression = if field.data_type().is_numeric() {
+                coalesce(vec![Expr::Column(column.clone()), lit(0)])
+            } else {
+                Expr::Column(column.clone())
+            };
+            expressions.push(expression.alias(&column.name));
+        }
+        let sort_expressions: Vec<SortExpr> = grid_columns
+            .iter()
+            .map(|column| ident(&column.name).sort(true, true))
+            .collect();
+
+        grid.join_detailed(
+            aggregated,
+            JoinType::Left,
+            (grid_columns.clone(), group_columns.to_vec()),
+            None,
+            NullEquality::NullEqualsNull,
+        )?
+        .project(expressions)?
+        .sort(sort_expressions)
+    }
+
+    /// Counts the values of `fields` and keeps the `limit` most frequent ones, or the least
+    /// frequent ones if `ascending`, within each group of `by`. Next to the count, each row gets
+    /// its share of the group total in percent.
+    fn plan_frequency(
+        &self,
+        builder: LogicalPlanBuilder,
+        limit: Option<i64>,
+        fields: Vec<Expression>,
+        by: Vec<Expression>,
+        ascending: bool,
+    ) -> Result<LogicalPlanBuilder> {
+        let limit = limit.unwrap_or(DEFAULT_FREQUENCY_LIMIT);
+        let by = by
+            .into_iter()
+            .map(|expression| self.map_group(expression))
+            .collect::<Result<Vec<_>>>()?;
+        let fields = fields
+            .into_iter()
+            .map(|expression| self.map_group(expression))
+            .collect::<Result<Vec<_>>>()?;
+        let group_expressions: Vec<Expr> = by.iter().chain(fields.iter()).cloned().collect();
+        let group_expressions_len = group_expressions.len();
+
+        let aggregated =
+            builder.aggregate(group_expressions, vec![count(lit(1)).alias("count")])?;
+        let mut columns = aggregated.schema().columns();
+        columns.truncate(group_expressions_len);
+        let (by_columns, field_columns) = columns.split_at(by.len());
+        let by_columns: Vec<Expr> = by_columns.iter().cloned().map(Expr::Column).collect();
+
+        let total = Expr::from(WindowFunction::new(sum_udaf(), vec![col("count")]))
+            .partition_by(by_columns.clone())
+            .build()?;
+
+        let mut rank_order = vec![col("count").sort(ascending, false)];
+        for column in field_columns {
+            rank_order.push(Expr::Column(column.clone()).sort(true, true));
+        }
+        let rank = row_number()
+            .partition_by(by_columns.clone())
+            .order_by(rank_order.clone())
+            .build()?;
+
+        let mut output: Vec<Expr> = columns.iter().cloned().map(Expr::Column).collect();
+        output.push(col("count"));
+        output.push((lit(100.0) * col("count") / col("__total")).alias("percent"));
+        let sort_expressions: Vec<SortExpr> = by_columns
+            .into_iter()
+            .map(|column| column.sort(true, true))
+            .chain(rank_order)
+            .collect();
+
+        self.window(aggregated, vec![(total, "__total"), (rank, "__rank")])?
+            .filter(col("__rank").lt_eq(lit(limit)))?
+            .project(output)?
+            .sort(sort_expressions)
+    }
+
+    /// Keeps the first `count` rows of every distinct combination of `fields`, in the order of
+    /// `sort` or, if it's empty, of the timestamp column when there is one, and else in the
+    /// order the rows come in. With `keep_first` unset, the last rows are kept instead. The
+    /// rows that are kept stay in the order of the events.
+    fn plan_dedup(
+        &self,
+        mut builder: LogicalPlanBuilder,
+        count: i64,
+        fields: Vec<Expression>,
+        keep_first: bool,
+        sort: Vec<SortExpression>,
+    ) -> Result<LogicalPlanBuilder> {
+        let fields = fields
+            .into_iter()
+            .map(|expression| self.map_expression(expression))
+            .collect::<Result<Vec<_>>>()?;
+        let columns: Vec<Expr> = builder
+            .schema()
+            .columns()
+            .into_iter()
+            .map(Expr::Column)
+            .collect();
+
+        // Without a timestamp, the rows are numbered as they come in, so that the last can be
+        // found and the order restored after the window.
+        let event_order = match self.timestamp_expression(&builder) {
+            Ok(timestamp) => timestamp.sort(true, false),
+            Err(_) => {
+                builder = self.window(builder, vec![(row_number(), "__position")])?;
+                col("__position").sort(true, false)
+            }
+        };
+        let mut order = self.map_sort_expressions(sort)?;
+        if order.is_empty() {
+            order.push(event_order.clone());
+        }
+        if !keep_first {
+            order = order.into_iter().map(|sort| sort.reverse()).collect();
+        }
+
+        let rank = row_number().partition_by(fields).order_by(order).build()?;
+        self.window(builder, vec![(rank, "__rank")])?
+            .filter(col("__rank").lt_eq(lit(count)))?
+            .sort(vec![event_order])?
+            .project(columns)
+    }
+
+    /// Adds the aggregates to every row, computed over the frame of the row within its group.
+    /// Aggregates replace columns of the same name.
+    fn plan_window_aggregates(
+        &self,
+        builder: LogicalPlanBuilder,
+        aggregates: Vec<(Expression, Option<String>)>,
+        by: Vec<Expression>,
+        order: Vec<SortExpr>,
+        frame: WindowFrame,
+    ) -> Result<LogicalPlanBuilder> {
+        let partition = self.map_expressions(by)?;
+        // Every aggregate function becomes a window column the aggregate expression refers to.
+        let mut windows: Vec<(Expr, String)> = Vec::new();
+        let mut columns = Vec::with_capacity(aggregates.len());
+        for (expression, alias) in aggregates {
+            let expression = self.map_expression(expression)?;
+            let name = alias.unwrap_or_else(|| expression.schema_name().to_string());
+            let mut has_aggregate = false;
+            let expression = expression
+                .transform(|expression| {
+                    let Expr::AggregateFunction(AggregateFunction { func, params }) = expression
+                    else {
+                        return Ok(Transformed::no(expression));
+                    };
+                    if !params.order_by.is_empty() {
+                        return Err(DataFusionError::Plan(format!(
+                            "Aggregate '{}' can't be ordered within a window",
+                            func.name(),
+                        )));
+                    }
+                    has_aggregate = true;
+                    let mut window = WindowFunction::new(func, params.args);
+                    window.params.partition_by = partition.clone();
+                    window.params.order_by = order.clone();
+                    window.params.window_frame = frame.clone();
+                    window.params.filter = params.filter;
+                    window.params.distinct = params.distinct;
+                    window.params.null_treatment = params.null_treatment;
+                    let window = Expr::from(window);
+                    let column = match windows.iter().find(|(existing, _)| *existing == window) {
+                        Some((_, column)) => column.clone(),
+                        None => {
+                            let column = format!("__window{}", windows.len());
+                            windows.push((window, column.clone()));
+                            column
+                        }
+                    };
+                    Ok(Transformed::yes(ident(column)))
+                })?
+                .data;
+            if !has_aggregate {
+                return Err(DataFusionError::Plan(format!(
+                    "'{}' has no aggregate function",
+                    name,
+                )));
+            }
+            columns.push((name.clone(), expression.alias(name)));
+        }
+
+        let windows = windows
+            .iter()
+            .map(|(expression, column)| (expression.clone(), column.as_str()))
+            .collect();
+        let builder = self.window(builder, windows)?;
+        let builder = self.project_columns(builder, columns)?;
+        let expressions: Vec<Expr> = builder
+            .schema()
+            .columns()
+            .into_iter()
+            .filter(|column| !column.name.starts_with("__window"))
+            .map(Expr::Column)
+            .collect();
+        builder.project(expressions)
+    }
+
+    /// Appends named window expressions to the plan. `window_plan` splits them into one window
+    /// node per distinct sort order but rejects aliases, so the outputs are renamed afterwards.
+    fn window(
+        &self,
+        builder: LogicalPlanBuilder,
+        expressions: Vec<(Expr, &str)>,
+    ) -> Result<LogicalPlanBuilder> {
+        let mut projection: Vec<Expr> = builder
+            .schema()
+            .columns()
+            .into_iter()
+            .map(Expr::Column)
+            .collect();
+        let plan = builder.build()?;
+        let mut window_expressions = Vec::with_capacity(expressions.len());
+        for (expression, name) in expressions {
+            // Qualify the columns up front, as the window output is named after the expression.
+            let expression = normalize_col(expression, &plan)?;
+            let column = Column::from_name(expression.schema_name().to_string());
+            projection.push(Expr::Column(column).alias(name));
+            window_expressions.push(expression);
+        }
+        LogicalPlanBuilder::from(LogicalPlanBuilder::window_plan(plan, window_expressions)?)
+            .project(projection)
+    }
+
+    /// Returns the designated timestamp column of the current plan, cast to UTC nanoseconds
+    /// unless it is stored that way already.
+    fn timestamp_expression(&self, builder: &LogicalPlanBuilder) -> Result<Expr> {
+        let field = builder
+            .schema()
+            .field_with_unqualified_name(self.timestamp_column)
+            .map_err(|_| {
+                DataFusionError::Plan(format!(
+                    "Timestamp column '{}' not found",
+                    self.timestamp_column,
+                ))
+            })?;
+        let expression = ident(self.timestamp_column);
+        if field.data_type() == &TIMESTAMP_DATA_TYPE {
+            Ok(expression)
+        } else {
+            Ok(cast(expression, TIMESTAMP_DATA_TYPE))
+        }
+    }
+
+    /// Sorts the rows by the timestamp column, if there is one. Windows return the rows grouped
+    /// by partition, so this restores the order of the events.
+    fn sort_by_time(&self, builder: LogicalPlanBuilder) -> Result<LogicalPlanBuilder> {
+        match self.timestamp_expression(&builder) {
+            Ok(timestamp) => builder.sort(vec![timestamp.sort(true, false)]),
+            Err(_) => Ok(builder),
+        }
+    }
+
+    /// Resolves field patterns against the schema of the current plan.
+    /// Included fields are ordered by the first pattern that matches them.
+    fn resolve_fields(
+        &self,
+        builder: &LogicalPlanBuilder,
+        mode: FieldsMode,
+        patterns: &[String],
+    ) -> Result<Vec<Expr>> {
+        let columns = builder.schema().columns();
+        let columns: Vec<Column> = match mode {
+            FieldsMode::Include => {
+                let mut selected: Vec<Column> = Vec::new();
+                for pattern in patterns {
+                    let matched: Vec<&Column> = columns
+                        .iter()
+                        .filter(|column| wildcard::matches(pattern, &column.name))
+                        .collect();
+                    if matched.is_empty() && !wildcard::is_wildcard(pattern) {
+                        return Err(DataFusionError::Plan(format!(
+                            "Field '{}' not found",
+                            pattern,
+                        )));
+                    }
+                    for column in matched {
+                        if !selected.contains(column) {
+                            selected.push(column.clone());
+                        }
+                    }
+                }
+                selected
+            }
+            FieldsMode::Exclude => columns
+                .into_iter()
+                .filter(|column| {
+                    !patterns
+                        .iter()
+                        .any(|pattern| wildcard::matches(pattern, &column.name))
+                })
+                .collect(),
+        };
+        if columns.is_empty() {
+            return Err(DataFusionError::Plan(
+                "No fields left after applying 'fields'".to_owned(),
+            ));
         }
+        Ok(columns.into_iter().map(Expr::Column).collect())
     }
 
     fn map_expression(&self, expression: Expression) -> Result<Expr> {
         match expression {
             Expression::Null => Ok(lit(Null)),
             Expression::Boolean(v) => Ok(lit(v)),
+            Expression::Integer(v) => Ok(lit(v)),
             Expression::Number(v) => Ok(lit(v)),
+            Expression::Duration(v) => Ok(lit(ScalarValue::new_interval_mdn(
+                0,
+                0,
+                time::duration_nanos(v)?,
+            ))),
+            Expression::Timestamp(v) => Ok(time::timestamp_literal(time::parse_timestamp(&v)?)),
             Expression::String(v) => Ok(lit(v)),
-            Expression::Field(v) => Ok(col(v)),
+            Expression::Field(v) => Ok(ident(v)),
+            Expression::GetField(expression, name) => {
+                Ok(get_field(self.map_expression(*expression)?, name))
+            }
+            Expression::Index(expression, index) => {
+                let expression = self.map_expression(*expression)?;
+                match *index {
+                    Expression::String(key) => Ok(get_field(expression, key)),
+                    // Arrays are indexed from 1, and negative indexes count from the end.
+                    Expression::Integer(n) => {
+                        let n = n.checked_add(1).ok_or_else(|| {
+                            DataFusionError::Plan(format!("Index {} is out of range", n))
+                        })?;
+                        Ok(array_element(expression, lit(n)))
+                    }
+                    Expression::Unary(UnaryOperator::Negate, n)
+                        if matches!(*n, Expression::Integer(_)) =>
+                    {
+                        Ok(array_element(expression, -self.map_expression(*n)?))
+                    }
+                    index => Ok(array_element(
+                        expression,
+                        self.map_expression(index)? + lit(1),
+                    )),
+                }
+            }
+            // Equality with null is never true in SQL, so `== null` tests for null instead.
+            Expression::Binary(
+                operator @ (BinaryOperator::Equal | BinaryOperator::NotEqual),
+                left,
+                right,
+            ) if matches!(*left, Expression::Null) || matches!(*right, Expression::Null) => {
+                let operand = match *left {
+                    Expression::Null => *right,
+                    left => left,
+                };
+                let operand = self.map_expression(operand)?;
+                match operator {
+                    BinaryOperator::Equal => Ok(operand.is_null()),
+                    _ => Ok(operand.is_not_null()),
+                }
+            }
             Expression::Binary(operator, left, right) => {
                 let left = Box::new(self.map_expression(*left)?);
                 let right = Box::new(self.map_expression(*right)?);
                 match operator {
                     BinaryOperator::And => Ok(left.and(*right)),
                     BinaryOperator::Or => Ok(left.or(*right)),
+                    BinaryOperator::Like => Ok(left.like(*right)),
+                    BinaryOperator::ILike => Ok(left.ilike(*right)),
                     _ => Ok(Expr::BinaryExpr(BinaryExpr {
                         left,
                         op: self.map_operator(operator)?,
@@ -100,29 +1268,91 @@ impl<'a> QueryPlanner<'a> {
                     })),
                 }
             }
-            Expression::Call(function_name, arguments) => {
-                let mut arguments: Vec<Expr> = arguments
-                    .into_iter()
-                    .map(|argument| self.map_expression(argument))
-                    .collect::<Result<Vec<_>>>()?;
-
-                // Hack: count(1) is equivalent to count(*).
-                if function_name == "count" && arguments.is_empty() {
-                    arguments.push(lit(1i64));
+            Expression::Unary(operator, expression) => {
+                let expression = self.map_expression(*expression)?;
+                match operator {
+                    UnaryOperator::Not => Ok(not(expression)),
+                    UnaryOperator::Negate => Ok(-expression),
                 }
-
+            }
+            Expression::In { expression, list } => Ok(self
+                .map_expression(*expression)?
+                .in_list(self.map_expressions(list)?, false)),
+            Expression::Between {
+                expression,
+                low,
+                high,
+            } => Ok(self
+                .map_expression(*expression)?
+                .between(self.map_expression(*low)?, self.map_expression(*high)?)),
+            Expression::Cast(expression, cast_type) => {
+                let data_type = match cast_type {
+                    CastType::Int => DataType::Int64,
+                    CastType::Float => DataType::Float64,
+                    CastType::Bool => DataType::Boolean,
+                    CastType::String => DataType::Utf8,
+                    CastType::Timestamp => TIMESTAMP_DATA_TYPE,
+                };
+                Ok(try_cast(self.map_expression(*expression)?, data_type))
+            }
+            Expression::IsNull(expression) => Ok(self.map_expression(*expression)?.is_null()),
+            Expression::Case { arms, otherwise } => {
+                let arms = arms
+                    .into_iter()
+                    .map(|(condition, value)| {
+                        Ok((
+                            Box::new(self.map_expression(condition)?),
+                            Box::new(self.map_expression(value)?),
+                        ))
+                    })
+                    .collect::<Result<_>>()?;
+                let otherwise = otherwise
+                    .map(|otherwise| self.map_expression(*otherwise).map(Box::new))
+                    .transpose()?;
+                Ok(Expr::Case(Case::new(None, arms, otherwise)))
+            }
+            Expression::Call {
+                name: function_name,
+                arguments,
+                distinct,
+                filter,
+                order_by,
+            } => {
                 if let Ok(aggregation_function) = self.context.udaf(&function_name) {
+                    let mut arguments = self.map_expressions(arguments)?;
+                    // Hack: count(1) is equivalent to count(*).
+                    if function_name == "count" && arguments.is_empty() {
+                        arguments.push(lit(1i64));
+                    }
+                    let filter = filter
+                        .map(|filter| self.map_expression(*filter).map(Box::new))
+                        .transpose()?;
                     return Ok(Expr::AggregateFunction(AggregateFunction::new_udf(
                         aggregation_function,
                         arguments,
-                        false,      // Distinct.
-                        None,       // Filter.
-                        Vec::new(), // Order by.
+                        distinct,
+                        filter,
+                        self.map_sort_expressions(order_by)?,
                         None,
                     )));
                 }
 
-                if let Some(function) = self.context.udf(&function_name).ok() {
+                if distinct || filter.is_some() || !order_by.is_empty() {
+                    return Err(DataFusionError::Plan(format!(
+                        "Function '{}' is not an aggregate function, so it doesn't support \
+                         distinct, where or order by",
+                        function_name,
+                    )));
+                }
+                match function_name.as_str() {
+                    "bin" => return self.map_bin(arguments),
+                    "to_number" | "to_timestamp" | "strftime" => {
+                        return self.map_conversion(&function_name, arguments);
+                    }
+                    _ => {}
+                }
+                let arguments = self.map_expressions(arguments)?;
+                if let Ok(function) = self.context.udf(&function_name) {
                     return Ok(Expr::ScalarFunction(ScalarFunction::new_udf(
                         function, arguments,
                     )));
@@ -135,6 +1365,147 @@ impl<'a> QueryPlanner<'a> {
         }
     }
 
+    /// Maps a condition, expanding strings in the place of a condition into a search of the
+    /// text fields of the current plan.
+    fn map_predicate(&self, builder: &LogicalPlanBuilder, expression: Expression) -> Result<Expr> {
+        match expression {
+            Expression::String(term) => Ok(self.text_search(builder, &term)),
+            Expression::Binary(
+                operator @ (BinaryOperator::And | BinaryOperator::Or),
+                left,
+                right,
+            ) => {
+                let left = self.map_predicate(builder, *left)?;
+                let right = self.map_predicate(builder, *right)?;
+                match operator {
+                    BinaryOperator::And => Ok(left.and(right)),
+                    _ => Ok(left.or(right)),
+                }
+            }
+            Expression::Unary(UnaryOperator::Not, expression) => {
+                Ok(not(self.map_predicate(builder, *expression)?))
+            }
+            expression => self.map_expression(expression),
+        }
+    }
+
+    /// Matches rows with any text field containing the term, ignoring case.
+    fn text_search(&self, builder: &LogicalPlanBuilder, term: &str) -> Expr {
+        let mut pattern = String::with_capacity(term.len() + 2);
+        pattern.push('%');
+        for character in term.chars() {
+            if matches!(character, '%' | '_' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(character);
+        }
+        pattern.push('%');
+
+        builder
+            .schema()
+            .iter()
+            .filter(|(_, field)| is_text(field.data_type()))
+            .map(|(qualifier, field)| {
+                let column = Expr::Column(Column::new(qualifier.cloned(), field.name()));
+                Expr::Like(Like::new(
+                    false,
+                    Box::new(column),
+                    Box::new(lit(pattern.clone())),
+                    Some('\\'),
+                    true,
+                ))
+            })
+            .reduce(Expr::or)
+            .unwrap_or(lit(false))
+    }
+
+    fn map_expressions(&self, expressions: Vec<Expression>) -> Result<Vec<Expr>> {
+        expressions
+            .into_iter()
+            .map(|expression| self.map_expression(expression))
+            .collect()
+    }
+
+    fn map_sort_expressions(&self, sort_expressions: Vec<SortExpression>) -> Result<Vec<SortExpr>> {
+        sort_expressions
+            .into_iter()
+            .map(|sort_expression| {
+                let expression = self.map_expression(sort_expression.expression)?;
+                let ascending = sort_expression.order == SortOrder::Ascending;
+                Ok(expression.sort(ascending, false))
+            })
+            .collect()
+    }
+
+    /// Maps `bin(value, span)`: timestamps are bucketed with `date_bin` when the span is a
+    /// duration, anything else is rounded down to a multiple of the span.
+    /// Maps a group of an aggregation. Groups can't be aliased, so a bin of a field, as in
+    /// `bin(_time, 1h)`, is named after the field for later commands to refer to it.
+    fn map_group(&self, expression: Expression) -> Result<Expr> {
+        let name = match &expression {
+            Expression::Call {
+                name, arguments, ..
+            } if name == "bin" => match arguments.first() {
+                Some(Expression::Field(field)) => Some(field.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let expression = self.map_expression(expression)?;
+        Ok(match name {
+            Some(name) => expression.alias(name),
+            None => expression,
+        })
+    }
+
+    fn map_bin(&self, arguments: Vec<Expression>) -> Result<Expr> {
+        let [value, span]: [Expression; 2] = arguments.try_into().map_err(|_| {
+            DataFusionError::Plan("Function 'bin' expects a value and a span".to_owned())
+        })?;
+        let is_duration = matches!(span, Expression::Duration(_));
+        let value = self.map_expression(value)?;
+        let span = self.map_expression(span)?;
+        if is_duration {
+            Ok(time::bin(span, cast(value, TIMESTAMP_DATA_TYPE)))
+        } else {
+            Ok(floor(value / span.clone()) * span)
+        }
+    }
+
+    /// Maps the conversion functions, which like casts turn values that can't be converted into
+    /// null rather than failing.
+    fn map_conversion(&self, function_name: &str, arguments: Vec<Expression>) -> Result<Expr> {
+        let mut arguments = self.map_expressions(arguments)?.into_iter();
+        match (
+            function_name,
+            arguments.next(),
+            arguments.next(),
+            arguments.next(),
+        ) {
+            ("to_number", Some(value), None, None) => Ok(try_cast(value, DataType::Float64)),
+            ("to_timestamp", Some(value), None, None) => Ok(try_cast(value, TIMESTAMP_DATA_TYPE)),
+            ("to_timestamp", Some(value), Some(format), None) => {
+                let function = ScalarUDF::from(ParseTimestamp::new());
+                Ok(function.call(vec![value, format]))
+            }
+            ("strftime", Some(timestamp), Some(format), None) => {
+                let function = self.context.udf("to_char")?;
+                Ok(function.call(vec![try_cast(timestamp, TIMESTAMP_DATA_TYPE), format]))
+            }
+            _ => {
+                let expected = match function_name {
+                    "to_number" => "a value",
+                    "to_timestamp" => "a value and optionally a format",
+                    _ => "a timestamp and a format",
+                };
+                Err(DataFusionError::Plan(format!(
+                    "Function '{}' expects {}",
+                    function_name, expected,
+                )))
+            }
+        }
+    }
+
     fn map_operator(&self, operator: BinaryOperator) -> Result<Operator> {
         match operator {
             BinaryOperator::Add => Ok(Operator::Plus),
@@ -147,12 +1518,40 @@ impl<'a> QueryPlanner<'a> {
             BinaryOperator::GreaterThanOrEqual => Ok(Operator::GtEq),
             BinaryOperator::LessThan => Ok(Operator::Lt),
             BinaryOperator::LessThanOrEqual => Ok(Operator::LtEq),
+            BinaryOperator::RegexMatch => Ok(Operator::RegexMatch),
+            BinaryOperator::RegexNotMatch => Ok(Operator::RegexNotMatch),
             BinaryOperator::And => {
                 unreachable!("Logical 'and' should be handled in expression builder")
             }
             BinaryOperator::Or => {
                 unreachable!("Logical 'or' should be handled in expression builder")
             }
+            BinaryOperator::Like | BinaryOperator::ILike => {
+                unreachable!("Pattern matching should be handled in expression builder")
+            }
         }
     }
 }
+
+/// Names an aggregate call after its function, followed by its field if it has a single one,
+/// as in `count` or `avg_latency`.
+fn default_aggregate_name(expression: &Expression) -> Option<String> {
+    let Expression::Call {
+        name, arguments, ..
+    } = expression
+    else {
+        return None;
+    };
+    match arguments.as_slice() {
+        [] => Some(name.clone()),
+        [Expression::Field(field)] => Some(format!("{}_{}", name, field)),
+        _ => None,
+    }
+}
+
+fn is_text(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    )
+}
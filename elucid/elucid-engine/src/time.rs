@@ -0,0 +1,284 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use datafusion::arrow::compute::kernels::cast_utils::string_to_timestamp_nanos;
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::common::ScalarValue;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::prelude::*;
+use elucid_language::{TimeBound, TimeRange};
+
+/// Data type every timestamp column is normalized to: UTC nanoseconds without a time zone.
+pub(crate) const TIMESTAMP_DATA_TYPE: DataType = DataType::Timestamp(TimeUnit::Nanosecond, None);
+
+/// Returns the current time as nanoseconds since the Unix epoch.
+pub(crate) fn now_nanos() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as i64)
+        .unwrap_or_default()
+}
+
+/// Concrete time range in nanoseconds since the Unix epoch. `start` is inclusive, `end` is
+/// exclusive, and a missing bound is unbounded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TimeWindow {
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+impl TimeWindow {
+    /// Resolves relative bounds against `now`, given in nanoseconds since the Unix epoch.
+    pub fn resolve(range: &TimeRange, now: i64) -> Result<Self> {
+        let resolve_bound = |bound: &Option<TimeBound>| {
+            bound
+                .as_ref()
+                .map(|bound| match bound {
+                    TimeBound::Relative(duration) => duration_nanos(*duration).and_then(|nanos| {
+                        now.checked_sub(nanos)
+                            .ok_or_else(|| out_of_range(*duration))
+                    }),
+                    TimeBound::Absolute(timestamp) => parse_timestamp(timestamp),
+                })
+                .transpose()
+        };
+        Ok(Self {
+            start: resolve_bound(&range.from)?,
+            end: resolve_bound(&range.to)?,
+        })
+    }
+
+    /// Returns `true` if any point of the closed interval `[min, max]` lies within the window.
+    pub fn overlaps(&self, min: i64, max: i64) -> bool {
+        self.start.is_none_or(|start| max >= start) && self.end.is_none_or(|end| min < end)
+    }
+
+    /// Returns the smallest window containing both windows.
+    pub fn hull(&self, other: &Self) -> Self {
+        Self {
+            start: self.start.zip(other.start).map(|(a, b)| a.min(b)),
+            end: self.end.zip(other.end).map(|(a, b)| a.max(b)),
+        }
+    }
+
+    /// Returns the window moved back in time by `nanos`. An open end stands for `now` then, as
+    /// the window would otherwise still reach into the present.
+    pub fn shift(&self, nanos: i64, now: i64) -> Result<Self> {
+        if nanos == 0 {
+            return Ok(*self);
+        }
+        let shift_bound = |bound: i64| {
+            bound
+                .checked_sub(nanos)
+                .ok_or_else(|| out_of_range(Duration::from_nanos(nanos.unsigned_abs())))
+        };
+        Ok(Self {
+            start: self.start.map(shift_bound).transpose()?,
+            end: Some(shift_bound(self.end.unwrap_or(now))?),
+        })
+    }
+
+    /// Builds a predicate restricting the timestamp expression to the window.
+    pub fn predicate(&self, timestamp: Expr) -> Option<Expr> {
+        let start = self
+            .start
+            .map(|start| timestamp.clone().gt_eq(timestamp_literal(start)));
+        let end = self.end.map(|end| timestamp.lt(timestamp_literal(end)));
+        match (start, end) {
+            (Some(start), Some(end)) => Some(start.and(end)),
+            (start, end) => start.or(end),
+        }
+    }
+}
+
+/// Parses an ISO-8601 timestamp or date into nanoseconds since the Unix epoch. Timestamps
+/// without an offset are taken to be in UTC, and seconds may be omitted.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Result<i64> {
+    // Arrow only parses times with seconds, so `HH:MM` is extended to `HH:MM:00`.
+    let bytes = timestamp.as_bytes();
+    let omits_seconds = bytes.len() >= 16
+        && timestamp.is_char_boundary(16)
+        && bytes[13] == b':'
+        && bytes.get(16) != Some(&b':');
+    let normalized = if omits_seconds {
+        format!("{}:00{}", &timestamp[..16], &timestamp[16..])
+    } else {
+        timestamp.to_owned()
+    };
+    string_to_timestamp_nanos(&normalized).map_err(|error| {
+        DataFusionError::Plan(format!("Invalid timestamp '{}': {}", timestamp, error))
+    })
+}
+
+/// Converts a duration to nanoseconds, failing if it does not fit into a timestamp.
+pub(crate) fn duration_nanos(duration: Duration) -> Result<i64> {
+    i64::try_from(duration.as_nanos()).map_err(|_| out_of_range(duration))
+}
+
+fn out_of_range(duration: Duration) -> DataFusionError {
+    DataFusionError::Plan(format!(
+        "Duration '{}' is out of range",
+        duration_label(duration)
+    ))
+}
+
+/// Adds a duration to a shift in nanoseconds, failing if the sum does not fit into a timestamp.
+pub(crate) fn add_duration(shift: i64, duration: Duration) -> Result<i64> {
+    duration_nanos(duration)?
+        .checked_add(shift)
+        .ok_or_else(|| out_of_range(duration))
+}
+
+/// Formats a duration in the largest unit it is a whole multiple of, as in `7d` or `90m`.
+pub(crate) fn duration_label(duration: Duration) -> String {
+    const UNITS: [(&str, u128); 7] = [
+        ("d", 86_400_000_000_000),
+        ("h", 3_600_000_000_000),
+        ("m", 60_000_000_000),
+        ("s", 1_000_000_000),
+        ("ms", 1_000_000),
+        ("us", 1_000),
+        ("ns", 1),
+    ];
+    let nanos = duration.as_nanos();
+    let (unit, size) = UNITS
+        .into_iter()
+        .find(|(_, size)| nanos.is_multiple_of(*size))
+        .expect("every duration is a whole number of nanoseconds");
+    format!("{}{}", nanos / size, unit)
+}
+
+/// Truncates a timestamp to the start of its bucket of width `span`, counted from the epoch.
+pub(crate) fn bin(span: Expr, timestamp: Expr) -> Expr {
+    date_bin(span, timestamp, timestamp_literal(0))
+}
+
+pub(crate) fn timestamp_literal(nanos: i64) -> Expr {
+    lit(ScalarValue::TimestampNanosecond(Some(nanos), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3_600_000_000_000;
+    /// 2026-10-17T00:00:00Z in nanoseconds since the Unix epoch.
+    const DAY_START: i64 = 1_792_195_200_000_000_000;
+
+    fn window(start: Option<i64>, end: Option<i64>) -> TimeWindow {
+        TimeWindow { start, end }
+    }
+
+    #[test]
+    fn parse_timestamp_formats() {
+        assert_eq!(parse_timestamp("2026-10-17").unwrap(), DAY_START);
+        assert_eq!(
+            parse_timestamp("2026-10-17T13:00").unwrap(),
+            DAY_START + 13 * HOUR
+        );
+        assert_eq!(
+            parse_timestamp("2026-10-17T13:00Z").unwrap(),
+            DAY_START + 13 * HOUR
+        );
+        assert_eq!(
+            parse_timestamp("2026-10-17T13:00:00Z").unwrap(),
+            DAY_START + 13 * HOUR
+        );
+        assert_eq!(
+            parse_timestamp("2026-10-17T18:00+05:00").unwrap(),
+            DAY_START + 13 * HOUR
+        );
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+
+    #[test]
+    fn resolve_rejects_durations_reaching_out_of_range() {
+        let range = |days: u64| TimeRange {
+            from: Some(TimeBound::Relative(Duration::from_secs(days * 86_400))),
+            to: None,
+        };
+        assert_eq!(
+            TimeWindow::resolve(&range(1), DAY_START).unwrap(),
+            window(Some(DAY_START - 24 * HOUR), None),
+        );
+        assert!(TimeWindow::resolve(&range(106_752), DAY_START).is_err());
+        assert!(TimeWindow::resolve(&range(200_000), DAY_START).is_err());
+    }
+
+    #[test]
+    fn overlaps_checks_the_closed_interval_against_the_half_open_window() {
+        let bounded = window(Some(10), Some(20));
+        assert!(bounded.overlaps(0, 10));
+        assert!(bounded.overlaps(19, 30));
+        assert!(!bounded.overlaps(0, 9));
+        assert!(!bounded.overlaps(20, 30));
+        assert!(window(None, Some(20)).overlaps(i64::MIN, 0));
+        assert!(window(Some(10), None).overlaps(100, i64::MAX));
+        assert!(window(None, None).overlaps(0, 0));
+    }
+
+    #[test]
+    fn hull_covers_both_windows() {
+        assert_eq!(
+            window(Some(10), Some(20)).hull(&window(Some(30), Some(40))),
+            window(Some(10), Some(40)),
+        );
+        assert_eq!(
+            window(None, Some(20)).hull(&window(Some(30), Some(40))),
+            window(None, Some(40)),
+        );
+        assert_eq!(
+            window(Some(10), Some(20)).hull(&window(Some(0), None)),
+            window(Some(0), None),
+        );
+    }
+
+    #[test]
+    fn duration_label_uses_the_largest_whole_unit() {
+        assert_eq!(duration_label(Duration::from_secs(7 * 86_400)), "7d");
+        assert_eq!(duration_label(Duration::from_secs(36 * 3_600)), "36h");
+        assert_eq!(duration_label(Duration::from_secs(90 * 60)), "90m");
+        assert_eq!(duration_label(Duration::from_secs(61)), "61s");
+        assert_eq!(duration_label(Duration::from_millis(1_500)), "1500ms");
+        assert_eq!(duration_label(Duration::from_micros(3)), "3us");
+        assert_eq!(duration_label(Duration::from_nanos(1_001)), "1001ns");
+    }
+
+    #[test]
+    fn shift_moves_both_bounds() {
+        let window = TimeWindow {
+            start: Some(100),
+            end: Some(200),
+        };
+        let shifted = TimeWindow {
+            start: Some(70),
+            end: Some(170),
+        };
+        assert_eq!(window.shift(30, 1000).unwrap(), shifted);
+        assert_eq!(window.shift(0, 1000).unwrap(), window);
+        let early = TimeWindow {
+            start: Some(-2),
+            end: Some(0),
+        };
+        assert!(early.shift(i64::MAX, 1000).is_err());
+    }
+
+    #[test]
+    fn shift_closes_an_open_end_at_now() {
+        let since = TimeWindow {
+            start: Some(900),
+            end: None,
+        };
+        let shifted = TimeWindow {
+            start: Some(800),
+            end: Some(900),
+        };
+        assert_eq!(since.shift(100, 1000).unwrap(), shifted);
+        assert_eq!(since.shift(0, 1000).unwrap(), since);
+    }
+
+    #[test]
+    fn parse_timestamp_rejects_non_ascii() {
+        assert!(parse_timestamp("aaaaaaaaaaaaa:aé").is_err());
+        assert!(parse_timestamp("2026-10-17T12:00é").is_err());
+    }
+}
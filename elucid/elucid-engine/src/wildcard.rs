@@ -0,0 +1,63 @@
+/// Returns `true` if the pattern contains at least one `*` wildcard.
+pub(crate) fn is_wildcard(pattern: &str) -> bool {
+    pattern.contains('*')
+}
+
+/// Matches a name against a pattern where `*` stands for any, possibly empty, sequence of
+/// characters. Any other character must match exactly.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (prefix, parts) = parts.split_first().expect("split yields at least one part");
+    let Some(mut rest) = name.strip_prefix(prefix) else {
+        return false;
+    };
+
+    let Some((suffix, middle)) = parts.split_last() else {
+        // No wildcard at all, so the prefix must be the whole name.
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= suffix.len() && rest.ends_with(suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn without_wildcard() {
+        assert!(matches("status", "status"));
+        assert!(!matches("status", "status_code"));
+        assert!(!matches("status", "stat"));
+    }
+
+    #[test]
+    fn prefix_and_suffix() {
+        assert!(matches("http_*", "http_status"));
+        assert!(matches("http_*", "http_"));
+        assert!(!matches("http_*", "https"));
+        assert!(matches("*_id", "user_id"));
+        assert!(!matches("*_id", "user_ids"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn several_wildcards() {
+        assert!(matches("a*b*c", "abc"));
+        assert!(matches("a*b*c", "a_b_b_c"));
+        assert!(!matches("a*b*c", "a_c_b"));
+        assert!(matches("*_*", "_"));
+    }
+
+    #[test]
+    fn overlapping_prefix_and_suffix() {
+        assert!(!matches("ab*ba", "aba"));
+        assert!(matches("ab*ba", "abba"));
+    }
+}
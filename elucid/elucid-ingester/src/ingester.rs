@@ -90,7 +90,7 @@ impl Ingester {
             None => return Ok(()),
         };
 
-        let file_name = format!("{}.parquet", Uuid::new_v4().to_string());
+        let file_name = format!("{}.parquet", Uuid::new_v4());
         let file_path = table_dir_path.join(file_name);
 
         let file = File::create(&file_path).await?;
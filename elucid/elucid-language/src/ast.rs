@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 #[derive(Debug, Clone)]
 pub enum BinaryOperator {
     Add,
@@ -12,17 +14,72 @@ pub enum BinaryOperator {
     LessThanOrEqual,
     And,
     Or,
+    Like,
+    ILike,
+    RegexMatch,
+    RegexNotMatch,
+}
+
+#[derive(Debug, Clone)]
+pub enum UnaryOperator {
+    Not,
+    Negate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CastType {
+    Int,
+    Float,
+    Bool,
+    String,
+    Timestamp,
 }
 
 #[derive(Debug, Clone)]
 pub enum Expression {
     Null,
     Boolean(bool),
+    Integer(i64),
     Number(f64),
+    Duration(Duration),
+    /// ISO-8601 timestamp or date, kept as written.
+    Timestamp(String),
     String(String),
     Field(String),
+    /// Field of a struct, as in `request.headers`.
+    GetField(Box<Expression>, String),
+    /// Element of a list by its 0-based position, or of a struct or map by its key.
+    Index(Box<Expression>, Box<Expression>),
+    /// Conversion written as `value::type`. Values that can't be converted become null.
+    Cast(Box<Expression>, CastType),
     Binary(BinaryOperator, Box<Expression>, Box<Expression>),
-    Call(String, Vec<Expression>),
+    Unary(UnaryOperator, Box<Expression>),
+    In {
+        expression: Box<Expression>,
+        list: Vec<Expression>,
+    },
+    /// Inclusive at both ends.
+    Between {
+        expression: Box<Expression>,
+        low: Box<Expression>,
+        high: Box<Expression>,
+    },
+    IsNull(Box<Expression>),
+    /// Value of the first arm whose condition holds, else of `otherwise`, else null. Written
+    /// as `if(condition, then, else)` or `case(condition, value, ..., [otherwise])`.
+    Case {
+        arms: Vec<(Expression, Expression)>,
+        otherwise: Option<Box<Expression>>,
+    },
+    /// Function call. The modifiers only apply to aggregate functions, as in
+    /// `count(distinct user)`, `count() where status >= 500` or `array_agg(msg order by _time)`.
+    Call {
+        name: String,
+        arguments: Vec<Expression>,
+        distinct: bool,
+        filter: Option<Box<Expression>>,
+        order_by: Vec<SortExpression>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -37,8 +94,16 @@ pub struct SortExpression {
     pub order: SortOrder,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldsMode {
+    Include,
+    Exclude,
+}
+
 #[derive(Debug, Clone)]
 pub enum Command {
+    /// Keeps the rows the condition holds for. A string in the place of a condition, as in
+    /// `where "timeout" and status >= 500`, holds if any text field contains it, ignoring case.
     Where(Expression),
     Sort(Vec<SortExpression>),
     Limit(i64),
@@ -46,10 +111,143 @@ pub enum Command {
         aggregates: Vec<(Expression, Option<String>)>,
         by: Vec<Expression>,
     },
+    Fields {
+        mode: FieldsMode,
+        patterns: Vec<String>,
+    },
+    Eval(Vec<(String, Expression)>),
+    Rename(Vec<(String, String)>),
+    Timechart {
+        span: Option<Duration>,
+        aggregates: Vec<(Expression, Option<String>)>,
+        by: Vec<Expression>,
+    },
+    /// Adds aggregates over all rows of each group to every row, keeping the rows.
+    EventStats {
+        aggregates: Vec<(Expression, Option<String>)>,
+        by: Vec<Expression>,
+    },
+    /// Adds running aggregates to every row, over the rows of its group up to itself in time
+    /// order, or over only the last `window` of them.
+    StreamStats {
+        window: Option<i64>,
+        aggregates: Vec<(Expression, Option<String>)>,
+        by: Vec<Expression>,
+    },
+    Top {
+        limit: Option<i64>,
+        fields: Vec<Expression>,
+        by: Vec<Expression>,
+    },
+    Rare {
+        limit: Option<i64>,
+        fields: Vec<Expression>,
+        by: Vec<Expression>,
+    },
+    Dedup {
+        count: Option<i64>,
+        fields: Vec<Expression>,
+        keep_first: bool,
+        sort: Vec<SortExpression>,
+    },
+    /// Extracts the named capture groups of `pattern` from `field` into columns.
+    Parse {
+        field: Expression,
+        pattern: String,
+    },
+    /// Like `Parse`, with a pattern made of `%{NAME:field:type}` grok references.
+    Grok {
+        field: Expression,
+        pattern: String,
+    },
+    /// Extracts every `key=value` pair of `field` into a column named `prefix` + key, replacing
+    /// any existing column of that name.
+    KeyValue {
+        field: Expression,
+        pair_delimiter: Option<String>,
+        kv_delimiter: Option<String>,
+        prefix: Option<String>,
+    },
+    /// Replaces nulls in the fields matching the patterns, or in all fields if there are none,
+    /// with `value`, which defaults to 0.
+    FillNull {
+        value: Option<Expression>,
+        patterns: Vec<String>,
+    },
+    /// Runs the pipeline so far again with the time range of `source` shifted back by each
+    /// duration, and joins the results onto the groups of the preceding aggregation, adding
+    /// the shifted values and their differences to the current ones.
+    Compare(Vec<Duration>),
+    /// Groups the values of `field` into templates whose variable parts are masked, with one
+    /// row per template holding its count, a sample and when it was first and last seen.
+    Patterns {
+        field: Expression,
+    },
+    /// Emits a row per element of the list in `field`, dropping rows whose list is empty or
+    /// null. With `flatten`, struct elements are replaced by a column per struct field.
+    Unnest {
+        field: String,
+        flatten: bool,
+    },
+    /// Left join against `table` on columns of the same name in both tables.
+    Lookup {
+        table: String,
+        fields: Vec<String>,
+    },
+    /// Join against the result of a sub-query. `on` is a conjunction of equalities, each
+    /// between an expression of this pipeline and one of the sub-query, or a single field name
+    /// shared by both.
+    Join {
+        kind: JoinKind,
+        query: Box<Query>,
+        on: Expression,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+#[derive(Debug, Clone)]
+pub enum TimeBound {
+    /// Point in time that lies the given duration before the query is executed.
+    Relative(Duration),
+    /// ISO-8601 timestamp or date.
+    Absolute(String),
+}
+
+/// Time range of a `source`, inclusive at the start and exclusive at the end.
+#[derive(Debug, Clone)]
+pub struct TimeRange {
+    pub from: Option<TimeBound>,
+    pub to: Option<TimeBound>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Query {
-    pub source: String,
+    /// Tables to read, where `*` matches any sequence of characters. Rows of several tables
+    /// are combined.
+    pub sources: Vec<String>,
+    pub time_range: Option<TimeRange>,
     pub commands: Vec<Command>,
 }
+
+#[derive(Debug, Clone)]
+pub enum Statement {
+    /// Binds the result of a query to a name that later statements can use as a `source`.
+    Let {
+        name: String,
+        query: Query,
+    },
+    Query(Query),
+}
+
+/// Sequence of statements separated by `;`.
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub statements: Vec<Statement>,
+}
@@ -1,4 +1,6 @@
 use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
 
 use logos::{Lexer, Logos};
 
@@ -9,14 +11,40 @@ pub fn tokenizer(source: &'_ str) -> impl Iterator<Item = (Token<'_>, Span)> {
         .spanned()
         .map(|(token, span)| match token {
             Ok(token) => (token, span.into()),
-            Err(()) => (Token::Error, span.into()),
+            Err(error) => (Token::Error(error), span.into()),
         })
 }
 
+/// Names of commands that are lexed as identifiers, as they are common field names too.
+pub const CONTEXTUAL_COMMANDS: [&str; 5] = ["search", "top", "rare", "kv", "patterns"];
+
+/// Reason the lexer rejected a token.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum LexError {
+    #[default]
+    UnexpectedCharacter,
+    /// Escape sequence of a string literal, with its own span rather than that of the literal.
+    InvalidEscape { escape: String, span: Range<usize> },
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedCharacter => write!(f, "unexpected character"),
+            Self::InvalidEscape { escape, .. } => {
+                write!(f, "invalid escape sequence '{}'", escape)
+            }
+        }
+    }
+}
+
 #[derive(Logos, Clone, Debug, PartialEq)]
+#[logos(error = LexError)]
 pub enum Token<'a> {
-    Error,
+    Error(LexError),
 
+    #[token("let")]
+    KeywordLet,
     #[token("source")]
     KeywordSource,
     #[token("where")]
@@ -29,6 +57,47 @@ pub enum Token<'a> {
     KeywordAggregate,
     #[token("by")]
     KeywordBy,
+    #[token("fields")]
+    KeywordFields,
+    #[token("eval")]
+    KeywordEval,
+    #[token("rename")]
+    KeywordRename,
+    #[token("timechart")]
+    KeywordTimechart,
+    #[token("eventstats")]
+    KeywordEventStats,
+    #[token("streamstats")]
+    KeywordStreamStats,
+    #[token("dedup")]
+    KeywordDedup,
+    #[token("parse")]
+    #[token("rex")]
+    KeywordParse,
+    #[token("grok")]
+    KeywordGrok,
+    #[token("fillnull")]
+    KeywordFillNull,
+    #[token("compare")]
+    #[token("timewrap")]
+    KeywordCompare,
+    #[token("unnest")]
+    #[token("mvexpand")]
+    KeywordUnnest,
+    #[token("lookup")]
+    KeywordLookup,
+    #[token("join")]
+    KeywordJoin,
+    #[token("on")]
+    KeywordOn,
+    #[token("as")]
+    KeywordAs,
+    #[token("true")]
+    KeywordTrue,
+    #[token("false")]
+    KeywordFalse,
+    #[token("null")]
+    KeywordNull,
 
     #[token("|")]
     Pipe,
@@ -36,6 +105,10 @@ pub enum Token<'a> {
     LeftParenthesis,
     #[token(")")]
     RightParenthesis,
+    #[token("[")]
+    LeftBracket,
+    #[token("]")]
+    RightBracket,
 
     #[token("+")]
     OperatorAdd,
@@ -61,36 +134,95 @@ pub enum Token<'a> {
     OperatorAnd,
     #[token("or")]
     OperatorOr,
+    #[token("not")]
+    OperatorNot,
+    #[token("=~")]
+    OperatorRegexMatch,
+    #[token("!~")]
+    OperatorRegexNotMatch,
+    #[token("in")]
+    KeywordIn,
+    #[token("between")]
+    KeywordBetween,
+    #[token("like")]
+    KeywordLike,
+    #[token("ilike")]
+    KeywordIlike,
+    #[token("is")]
+    KeywordIs,
     #[token("=")]
     OperatorAssign,
 
-    #[regex("-?[0-9]+", callback_integer)]
+    #[regex("[0-9]+", callback_integer)]
     Integer(i64),
-    #[regex(r#""([^"\\]|\\t|\\u|\\n|\\")*""#, callback_string)]
-    StringLiteral(&'a str),
+    #[regex(r"[0-9]+\.[0-9]+([eE][+-]?[0-9]+)?", callback_float)]
+    #[regex(r"[0-9]+[eE][+-]?[0-9]+", callback_float)]
+    Float(f64),
+    #[regex("[0-9]+(ns|us|ms|s|m|h|d|w)", callback_duration)]
+    Duration(Duration),
+    #[regex(
+        r"[0-9]{4}-[0-9]{2}-[0-9]{2}(T[0-9]{2}:[0-9]{2}(:[0-9]{2}(\.[0-9]+)?)?(Z|[+-][0-9]{2}(:?[0-9]{2})?)?)?",
+        callback_string
+    )]
+    Timestamp(&'a str),
+    #[regex(r#""([^"\\]|\\.)*""#, callback_string_literal)]
+    #[regex(r#"'([^'\\]|\\.)*'"#, callback_string_literal)]
+    #[regex(r#"r"[^"]*""#, callback_raw_string_literal)]
+    StringLiteral(String),
 
     #[regex("[a-zA-Z_][a-zA-Z0-9_]*", callback_string)]
+    #[regex("`[^`]+`", callback_quoted_identifier)]
     Identifier(&'a str),
 
     #[token(",")]
     Comma,
+    #[token(".")]
+    Dot,
+    #[token(";")]
+    Semicolon,
+    #[token("::")]
+    DoubleColon,
 
-    #[regex(r"[ \t\f\n]+", logos::skip)]
+    #[regex(r"[ \t\f\r\n]+", logos::skip)]
     Whitespace,
+    #[regex(r"(#|//)[^\n]*", logos::skip, allow_greedy = true)]
+    Comment,
 }
 
 impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Self::KeywordLet => write!(f, "let"),
             Self::KeywordSource => write!(f, "source"),
             Self::KeywordWhere => write!(f, "where"),
             Self::KeywordSort => write!(f, "sort"),
             Self::KeywordLimit => write!(f, "limit"),
             Self::KeywordAggregate => write!(f, "aggr"),
             Self::KeywordBy => write!(f, "by"),
+            Self::KeywordFields => write!(f, "fields"),
+            Self::KeywordEval => write!(f, "eval"),
+            Self::KeywordRename => write!(f, "rename"),
+            Self::KeywordTimechart => write!(f, "timechart"),
+            Self::KeywordEventStats => write!(f, "eventstats"),
+            Self::KeywordStreamStats => write!(f, "streamstats"),
+            Self::KeywordDedup => write!(f, "dedup"),
+            Self::KeywordParse => write!(f, "parse"),
+            Self::KeywordGrok => write!(f, "grok"),
+            Self::KeywordFillNull => write!(f, "fillnull"),
+            Self::KeywordCompare => write!(f, "compare"),
+            Self::KeywordUnnest => write!(f, "unnest"),
+            Self::KeywordLookup => write!(f, "lookup"),
+            Self::KeywordJoin => write!(f, "join"),
+            Self::KeywordOn => write!(f, "on"),
+            Self::KeywordAs => write!(f, "as"),
+            Self::KeywordTrue => write!(f, "true"),
+            Self::KeywordFalse => write!(f, "false"),
+            Self::KeywordNull => write!(f, "null"),
             Self::Pipe => write!(f, "|"),
             Self::LeftParenthesis => write!(f, "("),
             Self::RightParenthesis => write!(f, ")"),
+            Self::LeftBracket => write!(f, "["),
+            Self::RightBracket => write!(f, "]"),
             Self::OperatorAdd => write!(f, "+"),
             Self::OperatorSubtract => write!(f, "-"),
             Self::OperatorMultiply => write!(f, "+"),
@@ -103,13 +235,28 @@ impl fmt::Display for Token<'_> {
             Self::OperatorLessThanOrEqual => write!(f, "<="),
             Self::OperatorAnd => write!(f, "and"),
             Self::OperatorOr => write!(f, "or"),
+            Self::OperatorNot => write!(f, "not"),
+            Self::OperatorRegexMatch => write!(f, "=~"),
+            Self::OperatorRegexNotMatch => write!(f, "!~"),
+            Self::KeywordIn => write!(f, "in"),
+            Self::KeywordBetween => write!(f, "between"),
+            Self::KeywordLike => write!(f, "like"),
+            Self::KeywordIlike => write!(f, "ilike"),
+            Self::KeywordIs => write!(f, "is"),
             Self::OperatorAssign => write!(f, "="),
             Self::Integer(i) => write!(f, "{}", i),
-            Self::StringLiteral(s) => write!(f, "\"{}\"", s),
+            Self::Float(n) => write!(f, "{}", n),
+            Self::Duration(d) => write!(f, "{:?}", d),
+            Self::Timestamp(t) => write!(f, "{}", t),
+            Self::StringLiteral(s) => write!(f, "{:?}", s),
             Self::Identifier(i) => write!(f, "{}", i),
             Self::Comma => write!(f, ","),
+            Self::Dot => write!(f, "."),
+            Self::Semicolon => write!(f, ";"),
+            Self::DoubleColon => write!(f, "::"),
             Self::Whitespace => write!(f, "<whitespace>"),
-            Self::Error => write!(f, "<error>"),
+            Self::Comment => write!(f, "<comment>"),
+            Self::Error(_) => write!(f, "<error>"),
         }
     }
 }
@@ -118,6 +265,88 @@ fn callback_integer<'a>(lexer: &mut Lexer<'a, Token<'a>>) -> Option<i64> {
     lexer.slice().parse::<i64>().ok()
 }
 
+fn callback_float<'a>(lexer: &mut Lexer<'a, Token<'a>>) -> Option<f64> {
+    lexer.slice().parse::<f64>().ok()
+}
+
+fn callback_duration<'a>(lexer: &mut Lexer<'a, Token<'a>>) -> Option<Duration> {
+    let slice = lexer.slice();
+    let unit_start = slice.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = slice.split_at(unit_start);
+    let value = value.parse::<u64>().ok()?;
+    let duration = match unit {
+        "ns" => Duration::from_nanos(value),
+        "us" => Duration::from_micros(value),
+        "ms" => Duration::from_millis(value),
+        "s" => Duration::from_secs(value),
+        "m" => Duration::from_secs(value.checked_mul(60)?),
+        "h" => Duration::from_secs(value.checked_mul(60 * 60)?),
+        "d" => Duration::from_secs(value.checked_mul(24 * 60 * 60)?),
+        "w" => Duration::from_secs(value.checked_mul(7 * 24 * 60 * 60)?),
+        _ => return None,
+    };
+    Some(duration)
+}
+
 fn callback_string<'a>(lexer: &mut Lexer<'a, Token<'a>>) -> &'a str {
     lexer.slice()
 }
+
+/// Decodes a quoted string literal. The supported escapes are `\n`, `\r`, `\t`, `\0`, `\\`,
+/// `\"`, `\'` and `\u{...}` with up to six hexadecimal digits.
+fn callback_string_literal<'a>(lexer: &mut Lexer<'a, Token<'a>>) -> Result<String, LexError> {
+    let slice = lexer.slice();
+    let content = &slice[1..slice.len() - 1];
+    let content_start = lexer.span().start + 1;
+    let invalid_escape = |start: usize, escape: String| LexError::InvalidEscape {
+        span: content_start + start..content_start + start + escape.len(),
+        escape,
+    };
+    let mut value = String::with_capacity(content.len());
+    let mut characters = content.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            value.push(character);
+            continue;
+        }
+        let start = content.len() - characters.as_str().len() - 1;
+        let escaped = match characters.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(character @ ('\\' | '"' | '\'')) => character,
+            Some('u') => {
+                let rest = characters.as_str();
+                let code_point = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .filter(|(digits, _)| (1..=6).contains(&digits.len()))
+                    .and_then(|(digits, _)| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32);
+                let Some(character) = code_point else {
+                    let end = rest.find('}').map_or(0, |end| end + 1);
+                    return Err(invalid_escape(start, format!("\\u{}", &rest[..end])));
+                };
+                let end = rest.find('}').expect("closing brace") + 1;
+                characters = rest[end..].chars();
+                character
+            }
+            Some(character) => return Err(invalid_escape(start, format!("\\{}", character))),
+            None => return Err(invalid_escape(start, "\\".to_owned())),
+        };
+        value.push(escaped);
+    }
+    Ok(value)
+}
+
+/// Takes a raw string literal `r"..."` as written, which suits regular expressions.
+fn callback_raw_string_literal<'a>(lexer: &mut Lexer<'a, Token<'a>>) -> String {
+    let slice = lexer.slice();
+    slice[2..slice.len() - 1].to_owned()
+}
+
+fn callback_quoted_identifier<'a>(lexer: &mut Lexer<'a, Token<'a>>) -> &'a str {
+    let slice = lexer.slice();
+    &slice[1..slice.len() - 1]
+}
@@ -1,9 +1,14 @@
+use std::iter;
+
 use chumsky::input::{Stream, ValueInput};
 use chumsky::prelude::*;
 use chumsky::Parser;
 
-use crate::ast::{BinaryOperator, Command, Expression, Query};
-use crate::lexer::{tokenizer, Token};
+use crate::ast::{
+    BinaryOperator, CastType, Command, Expression, FieldsMode, JoinKind, Query, Script, Statement,
+    TimeBound, TimeRange, UnaryOperator,
+};
+use crate::lexer::{tokenizer, LexError, Token};
 use crate::parser_error::ParserError;
 use crate::span::Span;
 use crate::{SortExpression, SortOrder};
@@ -11,19 +16,61 @@ use crate::{SortExpression, SortOrder};
 pub struct QueryParser;
 
 pub fn parse(source: &'_ str) -> Result<Query, ParserError<'_>> {
+    check_tokens(source)?;
     let input = new_input(source);
     query_parser()
         .parse(input)
         .into_result()
-        .map_err(|errors| ParserError::new(errors))
+        .map_err(ParserError::new)
 }
 
 pub fn check(source: &'_ str) -> Result<(), ParserError<'_>> {
+    check_tokens(source)?;
     let input = new_input(source);
     query_parser()
         .check(input)
         .into_result()
-        .map_err(|errors| ParserError::new(errors))
+        .map_err(ParserError::new)
+}
+
+pub fn parse_script(source: &'_ str) -> Result<Script, ParserError<'_>> {
+    check_tokens(source)?;
+    let input = new_input(source);
+    script_parser()
+        .parse(input)
+        .into_result()
+        .map_err(ParserError::new)
+}
+
+pub fn check_script(source: &'_ str) -> Result<(), ParserError<'_>> {
+    check_tokens(source)?;
+    let input = new_input(source);
+    script_parser()
+        .check(input)
+        .into_result()
+        .map_err(ParserError::new)
+}
+
+/// Reports the tokens the lexer rejected with their reason, which the parser would only see as
+/// unexpected.
+fn check_tokens(source: &'_ str) -> Result<(), ParserError<'_>> {
+    let errors: Vec<_> = tokenizer(source)
+        .filter_map(|(token, span)| match token {
+            Token::Error(error) => {
+                let span = match &error {
+                    LexError::InvalidEscape { span, .. } => span.clone().into(),
+                    LexError::UnexpectedCharacter => span,
+                };
+                Some(Rich::custom(span, error))
+            }
+            _ => None,
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ParserError::new(errors))
+    }
 }
 
 fn new_input(source: &'_ str) -> impl ValueInput<'_, Token = Token<'_>, Span = Span> {
@@ -31,45 +78,97 @@ fn new_input(source: &'_ str) -> impl ValueInput<'_, Token = Token<'_>, Span = S
     Stream::from_iter(tokens).map((0..source.len()).into(), |(token, span)| (token, span))
 }
 
+fn script_parser<'tokens, 'source: 'tokens, I>()
+-> impl Parser<'tokens, I, Script, extra::Err<Rich<'tokens, Token<'source>, Span>>>
+where
+    I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
+{
+    let identifier = select! { Token::Identifier(i) => i.to_string() };
+
+    let binding = just(Token::KeywordLet)
+        .ignore_then(identifier)
+        .then_ignore(just(Token::OperatorAssign))
+        .then(query_parser())
+        .map(|(name, query)| Statement::Let { name, query });
+
+    binding
+        .or(query_parser().map(Statement::Query))
+        .separated_by(just(Token::Semicolon))
+        .allow_trailing()
+        .at_least(1)
+        .collect()
+        .map(|statements| Script { statements })
+}
+
 fn query_parser<'tokens, 'source: 'tokens, I>()
--> impl Parser<'tokens, I, Query, extra::Err<Rich<'tokens, Token<'source>, Span>>>
+-> impl Parser<'tokens, I, Query, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
+{
+    recursive(|query| {
+        let command = command_parser(query);
+
+        just(Token::KeywordSource)
+            .ignore_then(
+                pattern_parser("table")
+                    .separated_by(just(Token::Comma))
+                    .at_least(1)
+                    .collect(),
+            )
+            .then(time_range_parser())
+            .then(just(Token::Pipe).ignore_then(command).repeated().collect())
+            .map(|((sources, time_range), commands)| Query {
+                sources,
+                time_range,
+                commands,
+            })
+    })
+}
+
+fn time_range_parser<'tokens, 'source: 'tokens, I>()
+-> impl Parser<'tokens, I, Option<TimeRange>, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone
 where
     I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
 {
-    let command = command_parser();
+    let time_bound = select! {
+        Token::Duration(d) => TimeBound::Relative(d),
+        Token::Timestamp(t) => TimeBound::Absolute(t.to_owned()),
+        Token::StringLiteral(s) => TimeBound::Absolute(s),
+    };
 
-    just(Token::KeywordSource)
-        .ignore_then(select! { Token::Identifier(i) => i.to_owned() })
-        .then(just(Token::Pipe).ignore_then(command).repeated().collect())
-        .map(|(source, commands)| Query { source, commands })
+    let from = just(Token::Identifier("since"))
+        .or(just(Token::Identifier("from")))
+        .ignore_then(time_bound);
+    let to = just(Token::Identifier("to")).ignore_then(time_bound);
+
+    from.or_not()
+        .then(to.or_not())
+        .map(|(from, to)| match (from, to) {
+            (None, None) => None,
+            (from, to) => Some(TimeRange { from, to }),
+        })
 }
 
-fn command_parser<'tokens, 'source: 'tokens, I>()
--> impl Parser<'tokens, I, Command, extra::Err<Rich<'tokens, Token<'source>, Span>>>
+fn command_parser<'tokens, 'source: 'tokens, I, Q>(
+    query: Q,
+) -> impl Parser<'tokens, I, Command, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone
 where
     I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
+    Q: Parser<'tokens, I, Query, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone,
 {
     let expression = expression_parser();
 
     let identifier = select! { Token::Identifier(i) => i.to_string() };
 
     let command_where = just(Token::KeywordWhere)
+        .or(just(Token::Identifier("search")))
         .ignore_then(expression.clone())
         .map(Command::Where);
 
-    let sort_item = choice((
-        just(Token::OperatorSubtract)
-            .ignore_then(expression.clone())
-            .map(|expression| (expression, SortOrder::Descending)),
-        just(Token::OperatorAdd)
-            .ignore_then(expression.clone())
-            .map(|expression| (expression, SortOrder::Ascending)),
-        expression.clone().map(|expr| (expr, SortOrder::Ascending)),
-    ))
-    .map(|(expression, order)| SortExpression { expression, order });
+    let sort_items = sort_items_parser(expression.clone());
     let command_sort = just(Token::KeywordSort)
         .ignore_then(just(Token::KeywordBy).or_not())
-        .ignore_then(sort_item.separated_by(just(Token::Comma)).collect())
+        .ignore_then(sort_items.clone())
         .map(Command::Sort)
         .labelled("sort");
 
@@ -82,7 +181,9 @@ where
             .then_ignore(just(Token::OperatorAssign))
             .then(expression.clone())
             .map(|(alias, expression)| (expression, Some(alias))),
-        expression.clone().map(|expr| (expr, None)),
+        expression
+            .clone()
+            .then(just(Token::KeywordAs).ignore_then(identifier).or_not()),
     ));
     let by_clause = just(Token::KeywordBy)
         .ignore_then(
@@ -93,94 +194,624 @@ where
         )
         .or_not()
         .map(|option| option.unwrap_or_default());
+    let aggregation_items = aggregation_item
+        .separated_by(just(Token::Comma))
+        .collect::<Vec<_>>();
     let command_aggregate = just(Token::KeywordAggregate)
-        .ignore_then(aggregation_item.separated_by(just(Token::Comma)).collect())
-        .then(by_clause)
+        .ignore_then(aggregation_items.clone())
+        .then(by_clause.clone())
         .map(|(aggregates, by)| Command::Aggregate { aggregates, by });
 
+    let command_event_stats = just(Token::KeywordEventStats)
+        .ignore_then(aggregation_items.clone())
+        .then(by_clause.clone())
+        .map(|(aggregates, by)| Command::EventStats { aggregates, by });
+    let window = just(Token::Identifier("window"))
+        .ignore_then(just(Token::OperatorAssign))
+        .ignore_then(select! { Token::Integer(n) => n });
+    let command_stream_stats = just(Token::KeywordStreamStats)
+        .ignore_then(window.or_not())
+        .then(aggregation_items.clone())
+        .then(by_clause.clone())
+        .map(|((window, aggregates), by)| Command::StreamStats {
+            window,
+            aggregates,
+            by,
+        });
+
+    let frequency_arguments = select! { Token::Integer(n) => n }
+        .or_not()
+        .then(
+            expression
+                .clone()
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect(),
+        )
+        .then(by_clause.clone());
+    let command_top = just(Token::Identifier("top"))
+        .ignore_then(frequency_arguments.clone())
+        .map(|((limit, fields), by)| Command::Top { limit, fields, by });
+    let command_rare = just(Token::Identifier("rare"))
+        .ignore_then(frequency_arguments)
+        .map(|((limit, fields), by)| Command::Rare { limit, fields, by });
+
+    let boolean = select! {
+        Token::KeywordTrue => true,
+        Token::KeywordFalse => false,
+    };
+    let keep_first = just(Token::Identifier("keepfirst"))
+        .ignore_then(just(Token::OperatorAssign))
+        .ignore_then(boolean)
+        .map(|keep_first| ("keepfirst", DedupOption::KeepFirst(keep_first)));
+    let sort_by = just(Token::Identifier("sortby"))
+        .ignore_then(sort_items)
+        .map(|sort| ("sortby", DedupOption::SortBy(sort)));
+    let command_dedup = just(Token::KeywordDedup)
+        .ignore_then(select! { Token::Integer(n) => n }.or_not())
+        .then(
+            expression
+                .clone()
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect(),
+        )
+        .then(options_parser(keep_first.or(sort_by)))
+        .map(|((count, fields), options)| {
+            let mut keep_first = true;
+            let mut sort = Vec::new();
+            for (_, option) in options {
+                match option {
+                    DedupOption::KeepFirst(value) => keep_first = value,
+                    DedupOption::SortBy(value) => sort = value,
+                }
+            }
+            Command::Dedup {
+                count,
+                fields,
+                keep_first,
+                sort,
+            }
+        });
+
+    let string = select! { Token::StringLiteral(s) => s };
+    let command_parse = just(Token::KeywordParse)
+        .ignore_then(expression.clone())
+        .then(string)
+        .map(|(field, pattern)| Command::Parse { field, pattern });
+    let command_grok = just(Token::KeywordGrok)
+        .ignore_then(expression.clone())
+        .then(string)
+        .map(|(field, pattern)| Command::Grok { field, pattern });
+    let kv_option = choice(
+        ["pairdelim", "kvdelim", "prefix"].map(|name| just(Token::Identifier(name)).to(name)),
+    )
+    .then_ignore(just(Token::OperatorAssign))
+    .then(string.or(identifier));
+    let command_kv = just(Token::Identifier("kv"))
+        .ignore_then(expression.clone())
+        .then(options_parser(kv_option))
+        .map(|(field, options)| {
+            let option = |name| {
+                options
+                    .iter()
+                    .find(|(option, _)| *option == name)
+                    .map(|(_, value)| value.clone())
+            };
+            Command::KeyValue {
+                field,
+                pair_delimiter: option("pairdelim"),
+                kv_delimiter: option("kvdelim"),
+                prefix: option("prefix"),
+            }
+        });
+
+    let field_pattern = pattern_parser("field");
+    let fill_value = just(Token::Identifier("value"))
+        .ignore_then(just(Token::OperatorAssign))
+        .ignore_then(expression.clone());
+    let command_fill_null = just(Token::KeywordFillNull)
+        .ignore_then(fill_value.or_not())
+        .then(
+            field_pattern
+                .clone()
+                .separated_by(just(Token::Comma))
+                .collect(),
+        )
+        .map(|(value, patterns)| Command::FillNull { value, patterns });
+
+    let command_compare = just(Token::KeywordCompare)
+        .ignore_then(
+            select! { Token::Duration(d) => d }
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect(),
+        )
+        .map(Command::Compare);
+
+    let command_patterns = just(Token::Identifier("patterns"))
+        .ignore_then(expression.clone())
+        .map(|field| Command::Patterns { field });
+
+    let flatten = just(Token::Identifier("flatten"))
+        .ignore_then(just(Token::OperatorAssign))
+        .ignore_then(boolean);
+    let command_unnest = just(Token::KeywordUnnest)
+        .ignore_then(identifier)
+        .then(flatten.or_not())
+        .map(|(field, flatten)| Command::Unnest {
+            field,
+            flatten: flatten.unwrap_or(false),
+        });
+
+    let span = just(Token::Identifier("span"))
+        .ignore_then(just(Token::OperatorAssign))
+        .ignore_then(select! { Token::Duration(d) => d });
+    let command_timechart = just(Token::KeywordTimechart)
+        .ignore_then(span.or_not())
+        .then(aggregation_items)
+        .then(by_clause)
+        .map(|((span, aggregates), by)| Command::Timechart {
+            span,
+            aggregates,
+            by,
+        });
+
+    let fields_mode = choice((
+        just(Token::OperatorSubtract).to(FieldsMode::Exclude),
+        just(Token::OperatorAdd).to(FieldsMode::Include),
+    ))
+    .or_not()
+    .map(|option| option.unwrap_or(FieldsMode::Include));
+    let command_fields = just(Token::KeywordFields)
+        .ignore_then(fields_mode)
+        .then(
+            field_pattern
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect(),
+        )
+        .map(|(mode, patterns)| Command::Fields { mode, patterns });
+
+    let assignment = identifier
+        .then_ignore(just(Token::OperatorAssign))
+        .then(expression.clone());
+    let command_eval = just(Token::KeywordEval)
+        .ignore_then(
+            assignment
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect(),
+        )
+        .map(Command::Eval);
+
+    let renaming = identifier
+        .then_ignore(just(Token::KeywordAs))
+        .then(identifier);
+    let command_rename = just(Token::KeywordRename)
+        .ignore_then(
+            renaming
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect(),
+        )
+        .map(Command::Rename);
+
+    let command_lookup = just(Token::KeywordLookup)
+        .ignore_then(identifier)
+        .then_ignore(just(Token::KeywordOn))
+        .then(
+            identifier
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect(),
+        )
+        .map(|(table, fields)| Command::Lookup { table, fields });
+
+    let join_kind = just(Token::Identifier("type"))
+        .ignore_then(just(Token::OperatorAssign))
+        .ignore_then(select! { Token::Identifier(i) => i })
+        .try_map(|kind, span| match kind {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "right" => Ok(JoinKind::Right),
+            "full" | "outer" => Ok(JoinKind::Full),
+            _ => Err(Rich::custom(span, format!("unknown join type '{}'", kind))),
+        });
+    let command_join = just(Token::KeywordJoin)
+        .ignore_then(join_kind.or_not())
+        .then(query.delimited_by(just(Token::LeftBracket), just(Token::RightBracket)))
+        .then_ignore(just(Token::KeywordOn))
+        .then(expression.clone())
+        .map(|((kind, query), on)| Command::Join {
+            kind: kind.unwrap_or(JoinKind::Inner),
+            query: Box::new(query),
+            on,
+        });
+
     choice((
         command_where,
         command_sort,
         command_limit,
         command_aggregate,
+        command_fields,
+        command_eval,
+        command_rename,
+        command_timechart,
+        command_top,
+        command_rare,
+        command_event_stats,
+        command_stream_stats,
+        command_dedup,
+        command_parse,
+        command_grok,
+        command_kv,
+        command_fill_null,
+        command_unnest,
+        command_patterns,
+        command_compare,
+        command_lookup,
+        command_join,
     ))
 }
 
+/// Parses a name pattern like `http_*` or `*_id`, which is lexed as identifiers interleaved
+/// with `*`. The tokens of a pattern must not be separated by whitespace, so that neither
+/// adjacent names nor a following `since` are joined into it. `kind` names what the pattern
+/// matches in errors.
+fn pattern_parser<'tokens, 'source: 'tokens, I>(
+    kind: &'static str,
+) -> impl Parser<'tokens, I, String, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
+{
+    custom(move |input| {
+        let mut pattern = String::new();
+        let mut end: Option<usize> = None;
+        loop {
+            let checkpoint = input.save();
+            let before = input.cursor();
+            let token = input.next();
+            let span: Span = input.span_since(&before);
+            let is_adjacent = end.is_none_or(|end| span.start() == end);
+            match token {
+                Some(Token::Identifier(identifier)) if is_adjacent => pattern.push_str(identifier),
+                Some(Token::OperatorMultiply) if is_adjacent => pattern.push('*'),
+                _ if pattern.is_empty() => {
+                    return Err(Rich::custom(span, format!("expected a {} pattern", kind)));
+                }
+                _ => {
+                    input.rewind(checkpoint);
+                    return Ok(pattern);
+                }
+            }
+            end = Some(span.end());
+        }
+    })
+}
+
+/// Option of `dedup`, which may be given in any order.
+enum DedupOption {
+    KeepFirst(bool),
+    SortBy(Vec<SortExpression>),
+}
+
+/// Parses command options in any order, rejecting any given more than once. `option` parses
+/// a single option along with its name.
+fn options_parser<'tokens, 'source: 'tokens, I, T, P>(
+    option: P,
+) -> impl Parser<'tokens, I, Vec<(&'static str, T)>, extra::Err<Rich<'tokens, Token<'source>, Span>>>
+       + Clone
+where
+    I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
+    P: Parser<'tokens, I, (&'static str, T), extra::Err<Rich<'tokens, Token<'source>, Span>>>
+        + Clone,
+{
+    option
+        .map_with(|option, e| (option, e.span()))
+        .repeated()
+        .collect::<Vec<_>>()
+        .validate(|options, _, emitter| {
+            let mut unique: Vec<(&'static str, T)> = Vec::with_capacity(options.len());
+            for ((name, value), span) in options {
+                if unique.iter().any(|(existing, _)| *existing == name) {
+                    emitter.emit(Rich::custom(
+                        span,
+                        format!("option '{}' is given more than once", name),
+                    ));
+                } else {
+                    unique.push((name, value));
+                }
+            }
+            unique
+        })
+}
+
+fn sort_items_parser<'tokens, 'source: 'tokens, I, P>(
+    expression: P,
+) -> impl Parser<'tokens, I, Vec<SortExpression>, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
+    P: Parser<'tokens, I, Expression, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone,
+{
+    let sort_item = choice((
+        just(Token::OperatorSubtract)
+            .ignore_then(expression.clone())
+            .map(|expression| (expression, SortOrder::Descending)),
+        just(Token::OperatorAdd)
+            .ignore_then(expression.clone())
+            .map(|expression| (expression, SortOrder::Ascending)),
+        expression.map(|expr| (expr, SortOrder::Ascending)),
+    ))
+    .map(|(expression, order)| SortExpression { expression, order });
+
+    sort_item.separated_by(just(Token::Comma)).collect()
+}
+
 fn expression_parser<'tokens, 'source: 'tokens, I>()
 -> impl Parser<'tokens, I, Expression, extra::Err<Rich<'tokens, Token<'source>, Span>>> + Clone
 where
     I: ValueInput<'tokens, Token = Token<'source>, Span = Span>,
 {
     let identifier = select! { Token::Identifier(i) => i.to_string() };
-    let number = select! { Token::Integer(n) => Expression::Number(n as f64) };
-    let string_literal = select! { Token::StringLiteral(s) => Expression::String(s.to_owned()) };
+    let literal = select! {
+        Token::Integer(n) => Expression::Integer(n),
+        Token::Float(n) => Expression::Number(n),
+        Token::Duration(d) => Expression::Duration(d),
+        Token::Timestamp(t) => Expression::Timestamp(t.to_owned()),
+        Token::StringLiteral(s) => Expression::String(s),
+        Token::KeywordTrue => Expression::Boolean(true),
+        Token::KeywordFalse => Expression::Boolean(false),
+        Token::KeywordNull => Expression::Null,
+    };
 
     recursive(|expression| {
+        let arguments = expression.clone().separated_by(just(Token::Comma));
+        let distinct_arguments = choice((
+            just(Token::Identifier("distinct"))
+                .ignore_then(arguments.clone().at_least(1).collect())
+                .map(|arguments| (true, arguments)),
+            arguments
+                .clone()
+                .collect()
+                .map(|arguments| (false, arguments)),
+        ));
+        let order_by = just(Token::Identifier("order"))
+            .ignore_then(just(Token::KeywordBy))
+            .ignore_then(sort_items_parser(expression.clone()));
+        let filter = just(Token::KeywordWhere).ignore_then(expression.clone());
         let call = identifier
             .then_ignore(just(Token::LeftParenthesis))
-            .then(
-                expression
-                    .clone()
-                    .separated_by(just(Token::Comma))
-                    .collect(),
-            )
+            .then(distinct_arguments)
+            .then(order_by.or_not())
             .then_ignore(just(Token::RightParenthesis))
-            .map(|(name, arguments)| Expression::Call(name, arguments));
+            .then(filter.or_not())
+            .map(
+                |(((name, (distinct, arguments)), order_by), filter)| Expression::Call {
+                    name,
+                    arguments,
+                    distinct,
+                    filter: filter.map(Box::new),
+                    order_by: order_by.unwrap_or_default(),
+                },
+            );
+
+        let conditional = select! {
+            Token::Identifier("if") => true,
+            Token::Identifier("case") => false,
+        }
+        .then(
+            arguments
+                .clone()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::LeftParenthesis), just(Token::RightParenthesis)),
+        )
+        .validate(|(is_if, mut arguments), e, emitter| {
+            if is_if && arguments.len() != 3 {
+                emitter.emit(Rich::custom(
+                    e.span(),
+                    "if takes a condition, a value and an alternative",
+                ));
+            } else if arguments.len() < 2 {
+                emitter.emit(Rich::custom(
+                    e.span(),
+                    "case takes at least a condition and a value",
+                ));
+            }
+            let otherwise = if arguments.len() % 2 == 1 {
+                arguments.pop().map(Box::new)
+            } else {
+                None
+            };
+            let mut arguments = arguments.into_iter();
+            let arms = iter::from_fn(|| arguments.next().zip(arguments.next())).collect();
+            Expression::Case { arms, otherwise }
+        });
 
         let field = identifier.map(Expression::Field);
 
         let atom = choice((
-            number,
-            string_literal,
+            literal,
+            conditional,
             call,
             field,
-            expression.delimited_by(just(Token::LeftParenthesis), just(Token::RightParenthesis)),
-        ));
+            expression
+                .clone()
+                .delimited_by(just(Token::LeftParenthesis), just(Token::RightParenthesis)),
+        ))
+        .boxed();
 
-        let product = atom.clone().foldl(
+        let cast_type = select! { Token::Identifier(i) => i }.try_map(|name, span| match name {
+            "int" => Ok(CastType::Int),
+            "float" => Ok(CastType::Float),
+            "bool" => Ok(CastType::Bool),
+            "string" => Ok(CastType::String),
+            "timestamp" => Ok(CastType::Timestamp),
+            _ => Err(Rich::custom(span, format!("unknown type '{}'", name))),
+        });
+
+        let access = atom.foldl(
+            choice((
+                just(Token::Dot).ignore_then(identifier).map(Access::Field),
+                expression
+                    .clone()
+                    .delimited_by(just(Token::LeftBracket), just(Token::RightBracket))
+                    .map(Access::Index),
+                just(Token::DoubleColon)
+                    .ignore_then(cast_type)
+                    .map(Access::Cast),
+            ))
+            .repeated(),
+            |expression, access| match access {
+                Access::Field(name) => Expression::GetField(Box::new(expression), name),
+                Access::Index(index) => Expression::Index(Box::new(expression), Box::new(index)),
+                Access::Cast(cast_type) => Expression::Cast(Box::new(expression), cast_type),
+            },
+        );
+
+        let unary = just(Token::OperatorSubtract)
+            .repeated()
+            .foldr(access, |_, expression| {
+                Expression::Unary(UnaryOperator::Negate, Box::new(expression))
+            });
+
+        let product = unary.clone().foldl(
             just(Token::OperatorMultiply)
                 .to(BinaryOperator::Multiply)
                 .or(just(Token::OperatorDivide).to(BinaryOperator::Divide))
-                .then(atom)
+                .then(unary)
                 .repeated(),
             |l, (operator, r)| Expression::Binary(operator, Box::new(l), Box::new(r)),
         );
 
-        let sum = product.clone().foldl(
-            just(Token::OperatorAdd)
-                .to(BinaryOperator::Add)
-                .or(just(Token::OperatorSubtract).to(BinaryOperator::Subtract))
-                .then(product)
-                .repeated(),
-            |l, (operator, r)| Expression::Binary(operator, Box::new(l), Box::new(r)),
-        );
+        let sum = product
+            .clone()
+            .foldl(
+                just(Token::OperatorAdd)
+                    .to(BinaryOperator::Add)
+                    .or(just(Token::OperatorSubtract).to(BinaryOperator::Subtract))
+                    .then(product)
+                    .repeated(),
+                |l, (operator, r)| Expression::Binary(operator, Box::new(l), Box::new(r)),
+            )
+            .boxed();
 
-        let comparison = sum.clone().foldl(
-            just(Token::OperatorEqual)
-                .to(BinaryOperator::Equal)
-                .or(just(Token::OperatorNotEqual).to(BinaryOperator::NotEqual))
-                .or(just(Token::OperatorGreaterThan).to(BinaryOperator::GreaterThan))
-                .or(just(Token::OperatorGreaterThanOrEqual).to(BinaryOperator::GreaterThanOrEqual))
-                .or(just(Token::OperatorLessThan).to(BinaryOperator::LessThan))
-                .or(just(Token::OperatorLessThanOrEqual).to(BinaryOperator::LessThanOrEqual))
-                .then(sum)
-                .repeated(),
-            |l, (operator, r)| Expression::Binary(operator, Box::new(l), Box::new(r)),
-        );
+        let binary_predicate = choice((
+            just(Token::OperatorEqual).to(BinaryOperator::Equal),
+            just(Token::OperatorNotEqual).to(BinaryOperator::NotEqual),
+            just(Token::OperatorGreaterThan).to(BinaryOperator::GreaterThan),
+            just(Token::OperatorGreaterThanOrEqual).to(BinaryOperator::GreaterThanOrEqual),
+            just(Token::OperatorLessThan).to(BinaryOperator::LessThan),
+            just(Token::OperatorLessThanOrEqual).to(BinaryOperator::LessThanOrEqual),
+            just(Token::OperatorRegexMatch).to(BinaryOperator::RegexMatch),
+            just(Token::OperatorRegexNotMatch).to(BinaryOperator::RegexNotMatch),
+        ))
+        .then(sum.clone())
+        .map(|(operator, r)| Predicate::Binary(operator, r));
+        // Predicates that can be negated with a preceding `not`, as in `x not in (1, 2)`.
+        let negatable_predicate = just(Token::OperatorNot)
+            .or_not()
+            .map(|not| not.is_some())
+            .then(choice((
+                just(Token::KeywordLike)
+                    .to(BinaryOperator::Like)
+                    .or(just(Token::KeywordIlike).to(BinaryOperator::ILike))
+                    .then(sum.clone())
+                    .map(|(operator, r)| Predicate::Binary(operator, r)),
+                just(Token::KeywordIn)
+                    .ignore_then(
+                        expression
+                            .clone()
+                            .separated_by(just(Token::Comma))
+                            .collect()
+                            .delimited_by(
+                                just(Token::LeftParenthesis),
+                                just(Token::RightParenthesis),
+                            ),
+                    )
+                    .map(Predicate::In),
+                just(Token::KeywordBetween)
+                    .ignore_then(sum.clone())
+                    .then_ignore(just(Token::OperatorAnd))
+                    .then(sum.clone())
+                    .map(|(low, high)| Predicate::Between(low, high)),
+            )))
+            .map(|(negated, predicate)| match negated {
+                true => Predicate::Not(Box::new(predicate)),
+                false => predicate,
+            });
+        let null_predicate = just(Token::KeywordIs)
+            .ignore_then(just(Token::OperatorNot).or_not())
+            .then_ignore(just(Token::KeywordNull))
+            .map(|not| match not {
+                Some(_) => Predicate::Not(Box::new(Predicate::IsNull)),
+                None => Predicate::IsNull,
+            });
+
+        let comparison = sum
+            .foldl(
+                choice((binary_predicate, negatable_predicate, null_predicate)).repeated(),
+                |l, predicate| predicate.apply(l),
+            )
+            .boxed();
 
-        let logical = comparison.clone().foldl(
+        let negation = just(Token::OperatorNot)
+            .repeated()
+            .foldr(comparison, |_, expression| {
+                Expression::Unary(UnaryOperator::Not, Box::new(expression))
+            });
+
+        negation.clone().foldl(
             just(Token::OperatorAnd)
                 .to(BinaryOperator::And)
                 .or(just(Token::OperatorOr).to(BinaryOperator::Or))
-                .then(comparison)
+                .then(negation)
                 .repeated(),
             |l, (operator, r)| Expression::Binary(operator, Box::new(l), Box::new(r)),
-        );
-
-        logical
+        )
     })
 }
 
+/// Postfix access to a nested value, or conversion of a value.
+#[derive(Clone)]
+enum Access {
+    Field(String),
+    Index(Expression),
+    Cast(CastType),
+}
+
+/// Right-hand side of a comparison, applied to the left operand once it's parsed.
+#[derive(Clone)]
+enum Predicate {
+    Binary(BinaryOperator, Expression),
+    In(Vec<Expression>),
+    Between(Expression, Expression),
+    IsNull,
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    fn apply(self, l: Expression) -> Expression {
+        let l = Box::new(l);
+        match self {
+            Self::Binary(operator, r) => Expression::Binary(operator, l, Box::new(r)),
+            Self::In(list) => Expression::In {
+                expression: l,
+                list,
+            },
+            Self::Between(low, high) => Expression::Between {
+                expression: l,
+                low: Box::new(low),
+                high: Box::new(high),
+            },
+            Self::IsNull => Expression::IsNull(l),
+            Self::Not(predicate) => {
+                Expression::Unary(UnaryOperator::Not, Box::new(predicate.apply(*l)))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -226,6 +857,189 @@ mod tests {
 
         sort_mixed:
             "source test | sort by -count, +status, time",
+
+        fields_include:
+            "source test | fields status, http_*, *_id",
+
+        fields_exclude:
+            "source test | fields - debug_*, raw",
+
+        contextual_keywords_as_fields:
+            r#"source test since 1h | eval from = 1, to = 2 | where to == "x" | aggr count() by top, rare, kv, search, patterns, since"#,
+
+        fields_wildcards:
+            "source test | fields *, a*b*, *_id, **",
+
+        eval_assignments:
+            "source test | eval latency_ms = duration / 1000, is_error = status >= 500",
+
+        rename_quoted:
+            "source test | rename `user-agent` as agent, `http.status` as status",
+
+        source_since:
+            "source nginx since 1h | where status >= 500",
+
+        source_from_to:
+            r#"source nginx from "2026-10-01T00:00:00Z" to "2026-10-02""#,
+
+        typed_literals:
+            "source test | where ratio > 0.5 and ok == true and x != null and big < 1.5e3",
+
+        time_literals:
+            "source test | where _time > 2026-10-01T00:00Z and _time < 2026-10-02 and latency > 500ms",
+
+        timechart_span:
+            "source test | timechart span=5m count(), errors = sum(is_error) by service",
+
+        bin_call:
+            "source test | aggr c = count() by bin(_time, 1h)",
+
+        top_by:
+            "source test | top 5 path, method by host",
+
+        rare_default_limit:
+            "source test | rare user_agent",
+
+        dedup_key:
+            "source test | dedup request_id",
+
+        dedup_options:
+            "source test | dedup 3 host, error keepfirst=false sortby -_time",
+
+        dedup_options_in_any_order:
+            "source test | dedup host sortby -_time, id keepfirst=false",
+
+        aggregate_modifiers:
+            "source test | aggr users = count(distinct user), errors = count() where status >= 500 and ok == false, messages = array_agg(message order by -_time)",
+
+        parse_pattern:
+            r#"source test | parse message r"(?P<method>\w+) (?P<path>\S+) (?P<status:int>\d+)""#,
+
+        rex_alias:
+            r#"source test | rex message 'user=(?P<user>\\w+)'"#,
+
+        grok_pattern:
+            r#"source test | grok message r"%{IP:client} \[%{HTTPDATE:ts}\] %{NUMBER:bytes:int}""#,
+
+        kv_default:
+            "source test | kv message",
+
+        kv_options:
+            r#"source test | kv message pairdelim=";" kvdelim=":" prefix=kv_"#,
+
+        kv_options_in_any_order:
+            r#"source test | kv message prefix=kv_ pairdelim=";""#,
+
+        lookup_table:
+            "source test | lookup users on user_id, tenant",
+
+        join_subquery:
+            "source test | join type=left [source users since 1d | where active == true] on user_id == id and tenant",
+
+        multiple_sources:
+            "source api, worker, svc_* since 1h",
+
+        unary_operators:
+            "source test | where not -a > -1.5 and not b",
+
+        membership:
+            "source test | where status in (500, 502, 503) and host not in (a, b)",
+
+        between_range:
+            "source test | where duration between 100 and 500 and ok",
+
+        pattern_matching:
+            r#"source test | where path like "/api/%" or agent not ilike "%bot%" or message =~ "err(or)?" or host !~ "^db-""#,
+
+        null_checks:
+            "source test | where user is null or request is not null",
+
+        nested_access:
+            r#"source test | where request.headers.host == "a" and tags[0] == events[-1].name and labels["env"] == x"#,
+
+        string_escapes:
+            r#"source test | where a == "tab\there \"quoted\" \\ \u{1F600}" and b == 'it\'s' and c == r"C:\raw""#,
+
+        conditionals:
+            r#"source test | eval level = if(status >= 500, "error", "ok"), class = case(status < 300, "ok", status < 500, "client", "server"), tier = case(premium, 1)"#,
+
+        fill_null:
+            "source test | fillnull value=0 bytes, latency_* | fillnull",
+
+        type_casts:
+            r#"source test | where status::int >= 500 and -latency::float < 0 | eval t = to_timestamp(time, "%d/%b/%Y:%H:%M:%S %z"), day = strftime(_time, "%Y-%m-%d"), n = to_number(size), s = attrs.code::string"#,
+
+        unnest_list:
+            "source test | unnest tags | mvexpand events flatten=true",
+
+        event_stats:
+            "source test | eventstats avg(latency) as svc_avg, p = max(latency) by service",
+
+        stream_stats:
+            "source test | streamstats count() as n, sum(bytes) as running by user | streamstats window=5 avg(latency)",
+
+        text_search:
+            r#"source test | search "timeout" and "db-7" | where not "healthcheck" or status >= 500"#,
+
+        log_patterns:
+            "source test | patterns message | sort -count",
+
+        compare_shifts:
+            "source test since 1h | timechart span=5m count() by status | compare 1d, 7d | timewrap 1w",
+
+        quoted_identifier:
+            "source test | where `user-agent` == \"curl\" and `where` > 1",
+    }
+
+    #[test]
+    fn script_statements() {
+        let input = r#"
+            # Errors of the last hour.
+            let errors = source api since 1h | where level == "error";
+            // Count them per path.
+            source errors | aggr count() by path;
+            source errors | limit 10;
+        "#;
+        let script = parse_script(input);
+        assert!(script.is_ok());
+
+        insta::assert_debug_snapshot!(script.unwrap());
+    }
+
+    macro_rules! test_error_snapshots {
+        ( $($name:ident: $input:expr),* $(,)? ) => {
+            $(
+                #[test]
+                fn $name() {
+                    let input = $input;
+                    let error = parse(input).expect_err("input should not parse");
+                    insta::assert_debug_snapshot!(error);
+                }
+            )*
+        }
+    }
+
+    test_error_snapshots! {
+        fields_missing_comma:
+            "source test | fields status service",
+
+        sources_missing_comma:
+            "source api worker",
+
+        fillnull_missing_comma:
+            "source test | fillnull a b",
+
+        invalid_escape:
+            r#"source test | where path == "C:\\dir\q""#,
+
+        fields_spaced_wildcard:
+            "source test | fields http_ *",
+
+        dedup_duplicate_option:
+            "source test | dedup host keepfirst=false keepfirst=true",
+
+        kv_duplicate_option:
+            r#"source test | kv message prefix=a_ kvdelim=":" prefix=b_"#,
     }
 
     #[test]